@@ -0,0 +1,364 @@
+use crate::crypto::{self, VaultKey};
+use crate::error::{Error, Result};
+use base64::engine::general_purpose::STANDARD as base64_engine;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Requests the CLI sends, one JSON line per connection. Keys and vault contents are base64.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+enum Request {
+    Add { vault: PathBuf, key: String },
+    Decrypt { vault: PathBuf, data: String },
+    Encrypt { vault: PathBuf, data: String },
+    Forget { vault: PathBuf },
+    Lock,
+    Status,
+    Stop,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+enum Response {
+    Ok,
+    Data { data: String },
+    /// No key for this vault, or it timed out.
+    Locked,
+    Status { vaults: Vec<VaultStatus> },
+    Error { message: String },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VaultStatus {
+    pub vault: PathBuf,
+    pub idle_secs: u64,
+    /// Seconds until the idle or the absolute timeout, whichever comes first.
+    pub locks_in_secs: u64,
+}
+
+/// Whether the CLI should use the agent; `DPLOCK_AGENT=off` disables it.
+pub fn is_enabled() -> bool {
+    env::var("DPLOCK_AGENT").map(|v| v != "off" && v != "0").unwrap_or(true)
+}
+
+/// `DPLOCK_AGENT_SOCKET`, else `$XDG_RUNTIME_DIR/dplock-agent.sock`, else `~/.dplock/agent.sock`.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = env::var_os("DPLOCK_AGENT_SOCKET") {
+        return PathBuf::from(path);
+    }
+    if let Some(runtime) = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).filter(|p| p.is_dir()) {
+        return runtime.join("dplock-agent.sock");
+    }
+    dirs::home_dir().unwrap_or_default().join(".dplock/agent.sock")
+}
+
+fn timeout_from_env(var: &str, default: u64) -> Duration {
+    let secs = env::var(var)
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(default);
+    Duration::from_secs(secs)
+}
+
+/// The agent keys vaults by their canonical path, so `-v` spellings of one file share a key.
+fn vault_id(vault: &Path) -> PathBuf {
+    fs::canonicalize(vault).unwrap_or_else(|_| vault.to_path_buf())
+}
+
+fn request(request: &Request) -> Result<Response> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut line = Zeroizing::new(serde_json::to_vec(request).map_err(Error::other)?);
+    line.push(b'\n');
+    stream.write_all(&line)?;
+
+    let mut reply = Zeroizing::new(String::new());
+    BufReader::new(stream).read_line(&mut reply)?;
+    match serde_json::from_str(&reply).map_err(Error::other)? {
+        Response::Error { message } => Err(Error::Other(format!("Agent error: {}", message))),
+        response => Ok(response),
+    }
+}
+
+pub fn is_running() -> bool {
+    matches!(request(&Request::Status), Ok(Response::Status { .. }))
+}
+
+/// Hands the key for `vault` to the agent.
+pub fn add_key(vault: &Path, key: &VaultKey) -> Result<()> {
+    let key = Zeroizing::new(base64_engine.encode(key.to_bytes()));
+    request(&Request::Add { vault: vault_id(vault), key: key.to_string() })?;
+    Ok(())
+}
+
+/// Decrypts the vault `bytes` with the agent's key; `None` when the agent has no usable key.
+pub fn decrypt(vault: &Path, bytes: &[u8]) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    match request(&Request::Decrypt { vault: vault_id(vault), data: base64_engine.encode(bytes) })? {
+        Response::Data { data } => Ok(Some(decode(&Zeroizing::new(data))?)),
+        _ => Ok(None),
+    }
+}
+
+/// Encrypts a vault payload with the agent's key; `None` when the agent has no usable key.
+pub fn encrypt(vault: &Path, payload: &[u8]) -> Result<Option<Vec<u8>>> {
+    let data = Zeroizing::new(base64_engine.encode(payload));
+    match request(&Request::Encrypt { vault: vault_id(vault), data: data.to_string() })? {
+        Response::Data { data } => Ok(Some(decode(&data)?.to_vec())),
+        _ => Ok(None),
+    }
+}
+
+fn decode(data: &str) -> Result<Zeroizing<Vec<u8>>> {
+    base64_engine.decode(data.as_bytes()).map(Zeroizing::new).map_err(Error::other)
+}
+
+/// Drops the key for `vault`, e.g. after its master password changed. Quiet when no agent runs.
+pub fn forget(vault: &Path) {
+    let _ = request(&Request::Forget { vault: vault_id(vault) });
+}
+
+/// Wipes every key the agent holds. Returns `false` when no agent is running.
+pub fn lock() -> bool {
+    request(&Request::Lock).is_ok()
+}
+
+pub fn stop() -> bool {
+    request(&Request::Stop).is_ok()
+}
+
+/// The vaults the agent holds keys for, or `None` when no agent is running.
+pub fn status() -> Option<Vec<VaultStatus>> {
+    match request(&Request::Status) {
+        Ok(Response::Status { vaults }) => Some(vaults),
+        _ => None,
+    }
+}
+
+/// A key in memory that is never swapped out, and wiped when dropped.
+struct LockedKey {
+    key: Option<Box<VaultKey>>,
+    unlocked_at: Instant,
+    last_used: Instant,
+}
+
+impl LockedKey {
+    fn new(key: VaultKey) -> Self {
+        let key = Box::new(key);
+        // Best effort: without the privilege or with a low RLIMIT_MEMLOCK the key stays swappable.
+        unsafe { libc::mlock(&*key as *const VaultKey as *const libc::c_void, std::mem::size_of::<VaultKey>()) };
+        let now = Instant::now();
+        LockedKey { key: Some(key), unlocked_at: now, last_used: now }
+    }
+}
+
+impl Drop for LockedKey {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let address = &*key as *const VaultKey as usize;
+            drop(key); // zeroizes the key bytes
+            unsafe { libc::munlock(address as *const libc::c_void, std::mem::size_of::<VaultKey>()) };
+        }
+    }
+}
+
+struct Agent {
+    keys: HashMap<PathBuf, LockedKey>,
+    idle_timeout: Duration,
+    absolute_timeout: Duration,
+}
+
+impl Agent {
+    fn sweep(&mut self) {
+        let (idle, absolute) = (self.idle_timeout, self.absolute_timeout);
+        self.keys.retain(|_, key| key.last_used.elapsed() < idle && key.unlocked_at.elapsed() < absolute);
+    }
+
+    /// The key for `vault`, marked as used, unless it timed out.
+    fn key(&mut self, vault: &Path) -> Option<&VaultKey> {
+        self.sweep();
+        let key = self.keys.get_mut(vault)?;
+        key.last_used = Instant::now();
+        key.key.as_deref()
+    }
+
+    fn handle(&mut self, request: Request) -> Result<Response> {
+        Ok(match request {
+            Request::Add { vault, key } => {
+                let bytes = decode(&Zeroizing::new(key))?;
+                self.keys.insert(vault, LockedKey::new(VaultKey::from_bytes(&bytes)?));
+                Response::Ok
+            }
+            Request::Decrypt { vault, data } => match self.key(&vault) {
+                Some(key) => {
+                    let payload = crypto::decrypt_with_key(&decode(&data)?, key)?;
+                    Response::Data { data: base64_engine.encode(&*payload) }
+                }
+                None => Response::Locked,
+            },
+            Request::Encrypt { vault, data } => match self.key(&vault) {
+                Some(key) => {
+                    let payload = decode(&Zeroizing::new(data))?;
+                    Response::Data { data: base64_engine.encode(crypto::encrypt_with_key(&payload, key)?) }
+                }
+                None => Response::Locked,
+            },
+            Request::Forget { vault } => {
+                self.keys.remove(&vault);
+                Response::Ok
+            }
+            Request::Lock => {
+                self.keys.clear();
+                Response::Ok
+            }
+            Request::Status => {
+                self.sweep();
+                let vaults = self.keys.iter()
+                    .map(|(vault, key)| VaultStatus {
+                        vault: vault.clone(),
+                        idle_secs: key.last_used.elapsed().as_secs(),
+                        locks_in_secs: self.idle_timeout.saturating_sub(key.last_used.elapsed())
+                            .min(self.absolute_timeout.saturating_sub(key.unlocked_at.elapsed()))
+                            .as_secs(),
+                    })
+                    .collect();
+                Response::Status { vaults }
+            }
+            Request::Stop => {
+                self.keys.clear();
+                Response::Ok
+            }
+        })
+    }
+}
+
+/// Whether the other end of `stream` runs as our user; the socket mode is the first line of defence.
+fn peer_is_same_user(stream: &UnixStream) -> bool {
+    use std::os::unix::io::AsRawFd;
+    let uid = unsafe { libc::getuid() };
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let rc = unsafe {
+            libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, &mut cred as *mut _ as *mut libc::c_void, &mut len)
+        };
+        rc == 0 && cred.uid == uid
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        let (mut peer_uid, mut peer_gid) = (0, 0);
+        let rc = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut peer_uid, &mut peer_gid) };
+        rc == 0 && peer_uid == uid
+    }
+}
+
+/// Keeps keys out of core dumps and, on Linux, away from debuggers running as the same user.
+fn harden_process() {
+    let no_core = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    unsafe { libc::setrlimit(libc::RLIMIT_CORE, &no_core) };
+    #[cfg(target_os = "linux")]
+    unsafe {
+        libc::prctl(libc::PR_SET_DUMPABLE, 0);
+    }
+}
+
+fn bind(path: &Path) -> Result<UnixListener> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty() && !d.exists()) {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(Error::Other(format!("An agent is already listening on {}", path.display())));
+        }
+        fs::remove_file(path)?; // left over from an agent that didn't shut down cleanly
+    }
+    // Create the socket 0600 from the start rather than tightening it after bind.
+    let old_mask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(old_mask) };
+    let listener = listener.map_err(|e| Error::Other(format!("Failed to listen on {}: {e}", path.display())))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// The agent's socket, bound but not yet answering requests.
+pub struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+/// Hardens the process and binds the agent socket at `socket_path()`.
+pub fn listen() -> Result<Server> {
+    harden_process();
+    let path = socket_path();
+    let listener = bind(&path)?;
+    Ok(Server { listener, path })
+}
+
+impl Server {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Runs the agent in the foreground until `dplock agent stop`. Keys are dropped after
+    /// `DPLOCK_AGENT_IDLE_TIMEOUT` seconds without use (default: 900) and at the latest
+    /// `DPLOCK_AGENT_MAX_TIMEOUT` seconds after unlocking (default: 14400).
+    pub fn run(self) -> Result<()> {
+        serve(self.listener, &self.path)
+    }
+}
+
+fn serve(listener: UnixListener, path: &Path) -> Result<()> {
+    let agent = Arc::new(Mutex::new(Agent {
+        keys: HashMap::new(),
+        idle_timeout: timeout_from_env("DPLOCK_AGENT_IDLE_TIMEOUT", 900),
+        absolute_timeout: timeout_from_env("DPLOCK_AGENT_MAX_TIMEOUT", 4 * 3600),
+    }));
+    {
+        let agent = Arc::clone(&agent);
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(1));
+            if let Ok(mut agent) = agent.lock() {
+                agent.sweep();
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        if !peer_is_same_user(&stream) {
+            continue;
+        }
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        let mut line = Zeroizing::new(String::new());
+        if BufReader::new(&stream).read_line(&mut line).is_err() {
+            continue;
+        }
+        let request: Result<Request> = serde_json::from_str(&line).map_err(Error::other);
+        let stopping = matches!(request, Ok(Request::Stop));
+        let response = match request.and_then(|r| agent.lock().map_err(|_| Error::other("poisoned"))?.handle(r)) {
+            Ok(response) => response,
+            Err(e) => Response::Error { message: e.to_string() },
+        };
+        let mut reply = Zeroizing::new(serde_json::to_vec(&response).unwrap_or_default());
+        reply.push(b'\n');
+        let _ = (&stream).write_all(&reply);
+        if stopping {
+            break;
+        }
+    }
+
+    let _ = fs::remove_file(path);
+    Ok(())
+}
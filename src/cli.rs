@@ -1,4 +1,5 @@
-use clap::{arg, Command};
+use crate::clipboard;
+use clap::{arg, ArgAction, Command};
 
 fn init_subcommand() -> Command {
     Command::new("init")
@@ -12,21 +13,109 @@ fn add_subcommand() -> Command {
         .arg(arg!(<username> "Username"))
         .arg(arg!(-t --time "Enable expired/remind input (prompt for time)"))
         .arg(arg!(-m --message <MESSAGE> "Optional message or note for the entry"))
+        .arg(
+            arg!(-g --generate [POLICY] "Generate the password instead of prompting (e.g: len=24,chars=luds,no-ambiguous or words=6)")
+                .default_missing_value("")
+                .conflicts_with("password-stdin"),
+        )
+        .arg(arg!(--"password-stdin" "Read the entry's password from the first line of stdin instead of prompting"))
+        .arg(arg!(--totp "Prompt for a TOTP secret or otpauth:// URI to store with the entry"))
+        .arg(arg!(-T --template <TEMPLATE> "Entry type, which decides the fields prompted for: login, api-key, card, ssh-key, note (default: login)"))
+        .arg(arg!(-f --field <FIELD> "Add a custom field, as NAME=VALUE or NAME to be prompted (repeatable)").action(ArgAction::Append))
+        .arg(arg!(--"secret-field" <NAME> "Add a custom field whose value is prompted without echo and masked in output (repeatable)").action(ArgAction::Append))
+        .arg(arg!(--tag <TAG> "Tag the entry (repeatable)").action(ArgAction::Append))
+}
+
+fn otp_subcommand() -> Command {
+    Command::new("otp")
+        .about("Copy the current TOTP code for an entry")
+        .arg(arg!(<name> "Entry name"))
+        .arg(arg!(<username> "Username").required(false))
+        .arg(arg!(-S --show "Print the code instead of copying"))
+        .arg(arg!(--"no-clear" "Leave the copied code on the clipboard instead of clearing it after DPLOCK_CLIPBOARD_TIMEOUT"))
+        .arg(arg!(--set "Store a TOTP secret or otpauth:// URI on the entry"))
 }
 
 fn get_subcommand() -> Command {
     Command::new("get")
-        .about("Get a password by name")
-        .arg(arg!(<name> "Entry name"))
+        .about("Get a password by name (fuzzy match; pick interactively when several entries match)")
+        .arg(arg!(<name> "Entry name, or part of it"))
+        .arg(arg!(-e --exact "Match the name exactly").conflicts_with("regex"))
+        .arg(arg!(-r --regex "Treat the name as a regular expression and show every match"))
         .arg(arg!(<username> "Username").required(false))
+        .arg(arg!(-f --field <FIELD> "Copy (or with --show, print) a single custom field instead of the password"))
         .arg(arg!(-S --show "Print password instead of copying"))
+        .arg(arg!(--"no-clear" "Leave the copied password on the clipboard instead of clearing it after DPLOCK_CLIPBOARD_TIMEOUT"))
+}
+
+fn qr_subcommand() -> Command {
+    Command::new("qr")
+        .about("Show an entry's TOTP enrollment (or Wi-Fi credentials) as a QR code")
+        .arg(arg!(<name> "Entry name"))
+        .arg(arg!(<username> "Username").required(false))
+        .arg(
+            arg!(-w --wifi [SECURITY] "Encode a Wi-Fi login (username = SSID) instead: WPA, WEP or nopass")
+                .default_missing_value("WPA"),
+        )
+        .arg(arg!(--svg <PATH> "Write the QR code to an SVG file instead of the terminal"))
+}
+
+fn edit_subcommand() -> Command {
+    Command::new("edit")
+        .about("Change an entry in place, keeping its position and creation date")
+        .arg(arg!(<name> "Entry name"))
+        .arg(arg!(-i --index <INDEX> "Entry to edit when the name has several (1-based)").value_parser(clap::value_parser!(usize)))
+        .arg(arg!(-u --username <USERNAME> "New username"))
+        .arg(arg!(-p --password "Prompt for a new password").conflicts_with("generate"))
+        .arg(
+            arg!(-g --generate [POLICY] "Generate a new password (same policy syntax as add)")
+                .default_missing_value(""),
+        )
+        .arg(arg!(-m --message <MESSAGE> "New message (empty to remove it)"))
+        .arg(arg!(--expires <DURATION> "Expire after this duration from now (e.g: 3d, 1w, 3m), or 'none'"))
+        .arg(arg!(--remind <DURATION> "Remind this long before the expiry (e.g: 3d, 1w), or 'none'"))
+        .arg(arg!(-T --template <TEMPLATE> "Change the entry type: login, api-key, card, ssh-key, note"))
+        .arg(arg!(-f --field <FIELD> "Set a custom field, as NAME=VALUE or NAME to be prompted (repeatable)").action(ArgAction::Append))
+        .arg(arg!(--"secret-field" <NAME> "Set a secret custom field, prompted without echo (repeatable)").action(ArgAction::Append))
+        .arg(arg!(--"remove-field" <NAME> "Remove a custom field (repeatable)").action(ArgAction::Append))
+        .arg(arg!(--tag <TAG> "Add a tag (repeatable)").action(ArgAction::Append))
+        .arg(arg!(--untag <TAG> "Remove a tag (repeatable)").action(ArgAction::Append))
+        .arg(
+            arg!(-e --editor "Edit the whole entry as JSON in $EDITOR (via a temporary file in RAM)")
+                .conflicts_with_all(["username", "password", "generate", "message", "expires", "remind", "template", "field", "secret-field", "remove-field", "tag", "untag"]),
+        )
+}
+
+fn history_subcommand() -> Command {
+    Command::new("history")
+        .about("Show the previous passwords of an entry, or roll back to one")
+        .arg(arg!(<name> "Entry name"))
+        .arg(arg!(-i --index <INDEX> "Entry to use when the name has several (1-based)").value_parser(clap::value_parser!(usize)))
+        .arg(arg!(-r --restore <N> "Make previous password N the current password").value_parser(clap::value_parser!(usize)))
+        .arg(arg!(-S --show "Print previous passwords instead of masking them"))
+}
+
+fn mv_subcommand() -> Command {
+    Command::new("mv")
+        .about("Rename an entry name, or move one entry to another name")
+        .arg(arg!(<source> "Entry name to move from"))
+        .arg(arg!(<target> "Entry name to move to"))
+        .arg(arg!(-i --index <INDEX> "Move only this entry (1-based)").value_parser(clap::value_parser!(usize)))
+        .arg(
+            arg!(-c --"on-conflict" <MODE> "When the target has an entry with the same username: abort, skip or merge")
+                .value_parser(["abort", "skip", "merge"])
+                .default_value("abort"),
+        )
 }
 
 fn list_subcommand() -> Command {
     Command::new("list")
         .about("List all saved entries")
         .arg(arg!(-f --filter <KEY> "Filter entries by name or username"))
+        .arg(arg!(-t --tag <TAG> "Only entries with this tag (repeatable; all must match)").action(ArgAction::Append))
+        .arg(arg!(-F --folder <FOLDER> "Only entries in this folder, e.g: work/aws"))
         .arg(arg!(-s --sort <FIELD> "Sort by 'name' or 'username'"))
+        .arg(arg!(--tree "Show entries as a folder tree"))
 }
 
 fn remove_subcommand() -> Command {
@@ -51,21 +140,169 @@ fn import_subcommand() -> Command {
         .arg(arg!(-p --plain "Import passwords as plain text (⚠️ unsafe)"))
 }
 
+fn passwd_subcommand() -> Command {
+    Command::new("passwd")
+        .about("Change the master password and re-encrypt the vault")
+        .arg(arg!(-e --export <PATH> "Also re-encrypt an exported JSON file with the new password"))
+}
+
+fn backups_subcommand() -> Command {
+    Command::new("backups")
+        .about("List or restore automatic vault backups")
+        .subcommand_required(true)
+        .subcommand(Command::new("list").about("List available backups (1 is the most recent)"))
+        .subcommand(
+            Command::new("restore")
+                .about("Restore the vault from a backup")
+                .arg(arg!(<n> "Backup number to restore (see 'backups list')")),
+        )
+}
+
+fn generate_subcommand() -> Command {
+    Command::new("generate")
+        .about("Generate a random password or passphrase")
+        .arg(arg!(-l --length <LENGTH> "Password length (default: 20)"))
+        .arg(arg!(--"no-lower" "Exclude lowercase letters"))
+        .arg(arg!(--"no-upper" "Exclude uppercase letters"))
+        .arg(arg!(--"no-digits" "Exclude digits"))
+        .arg(arg!(--"no-symbols" "Exclude symbols"))
+        .arg(arg!(-x --"exclude-ambiguous" "Exclude look-alike characters such as l, 1, O and 0"))
+        .arg(arg!(--"no-require" "Do not guarantee one character from every enabled class"))
+        .arg(arg!(-p --passphrase "Generate a diceware-style passphrase instead"))
+        .arg(arg!(-w --words <WORDS> "Number of words in the passphrase (default: 6)"))
+        .arg(arg!(--separator <SEP> "Separator between passphrase words (default: -)"))
+        .arg(arg!(-n --count <COUNT> "Number of results to generate (default: 1)"))
+}
+
 fn check_reminders_subcommand() -> Command {
     Command::new("check-reminders")
         .about("Check all entries and notify if any password is due for review (remind date reached)")
+        .arg(arg!(-w --within <DURATION> "Also report entries expiring or due for review within this window (e.g: 7d, 2w)"))
+        .arg(arg!(--json "Print the result as JSON (same as --format json)"))
+}
+
+fn run_subcommand() -> Command {
+    Command::new("run")
+        .about("Run a command with secrets from the vault in its environment")
+        .arg(arg!(-e --env <MAPPING> "Set a variable to a secret, as VAR=name[:field] (repeatable; field: password, username, otp or a custom field)").action(ArgAction::Append))
+        .arg(arg!(-c --config <PATH> "Read mappings from this file instead of the nearest .dplock.toml"))
+        .arg(arg!(<command> ... "Command to run, after --").trailing_var_arg(true))
+}
+
+fn inject_subcommand() -> Command {
+    Command::new("inject")
+        .about("Render a template, replacing {{ dplock \"name\" \"field\" \"username\" }} with values from the vault")
+        .arg(arg!(-i --input <TEMPLATE> "Template file to render").required(true))
+        .arg(arg!(-o --output <PATH> "File to write (created with mode 0600)").required_unless_present("check"))
+        .arg(arg!(--strict "Fail without writing anything if a reference cannot be resolved"))
+        .arg(arg!(--check "Only verify that every reference resolves; write nothing").conflicts_with("output"))
+}
+
+fn tui_subcommand() -> Command {
+    Command::new("tui")
+        .about("Browse, copy and edit entries in a full-screen interface")
+}
+
+fn agent_subcommand() -> Command {
+    Command::new("agent")
+        .about("Keep unlocked vault keys in a background agent so commands don't prompt every time")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("start")
+                .about("Start the agent (it also starts on the first unlock)")
+                .arg(arg!(--foreground "Stay in the foreground instead of detaching")),
+        )
+        .subcommand(Command::new("stop").about("Wipe all keys and stop the agent"))
+        .subcommand(Command::new("status").about("Show whether the agent runs and which vaults it holds"))
+}
+
+fn keyfile_subcommand() -> Command {
+    Command::new("keyfile")
+        .about("Manage keyfiles, a second factor needed next to the master password")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("generate")
+                .about("Write a new random keyfile (never overwrites a file)")
+                .arg(arg!(<path> "Where to write it, e.g. on a USB stick")),
+        )
+}
+
+fn recovery_subcommand() -> Command {
+    Command::new("recovery")
+        .about("Split a recovery key into shares that can reset a forgotten master password")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("create")
+                .about("Create a recovery kit (replaces an earlier one)")
+                .arg(arg!(--shares <N> "Number of shares to print")
+                    .value_parser(clap::value_parser!(u8).range(1..))
+                    .required(true))
+                .arg(arg!(--threshold <M> "Number of shares needed to reset the master password")
+                    .value_parser(clap::value_parser!(u8).range(1..))
+                    .required(true))
+                .arg(arg!(--qr "Also print each share as a QR code"))
+                .arg(arg!(--svg <DIR> "Also write each share as a QR code to DIR/share-<N>.svg")),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Read shares from stdin and set a new master password")
+                .arg(arg!(--"new-password-file" <PATH> "Read the new master password from the first line of this file instead of prompting"))
+                .arg(arg!(--"drop-keyfile" "Reset a vault that requires a keyfile without one; it no longer requires a keyfile afterwards")),
+        )
+}
+
+fn lock_subcommand() -> Command {
+    Command::new("lock")
+        .about("Wipe all keys from the agent now; the next command asks for the master password")
+}
+
+/// Internal: the detached process that owns and later clears the clipboard.
+fn clipboard_guard_subcommand() -> Command {
+    Command::new(clipboard::GUARD_COMMAND)
+        .hide(true)
+        .arg(arg!(<timeout> "Seconds before clearing (0 = never)").value_parser(clap::value_parser!(u64)))
 }
 
 pub fn build_cli() -> Command {
     Command::new("dplock")
         .about("Minimal password manager — offline and secure")
         .arg(arg!(-v --vault <VAULT> "Specify the vault file path").global(true))
+        .arg(arg!(--format <FORMAT> "Output format of get, list and check-reminders")
+            .value_parser(["text", "json"])
+            .default_value("text")
+            .global(true))
+        .arg(arg!(--"password-fd" <FD> "Read the master password from this file descriptor instead of prompting")
+            .value_parser(clap::value_parser!(u32))
+            .global(true))
+        .arg(arg!(--"password-file" <PATH> "Read the master password from the first line of this file instead of prompting")
+            .conflicts_with("password-fd")
+            .global(true))
+        .arg(arg!(--keyfile <PATH> "Keyfile to unlock the vault with, next to the master password (also: DPLOCK_KEYFILE). With init, the new vault requires it")
+            .global(true))
+        .arg(arg!(--"no-emoji" "Print error messages as plain text (also: DPLOCK_NO_EMOJI)")
+            .global(true))
         .subcommand(init_subcommand())
         .subcommand(add_subcommand())
         .subcommand(get_subcommand())
+        .subcommand(otp_subcommand())
+        .subcommand(qr_subcommand())
+        .subcommand(edit_subcommand())
+        .subcommand(history_subcommand())
+        .subcommand(mv_subcommand())
         .subcommand(list_subcommand())
         .subcommand(remove_subcommand())
         .subcommand(export_subcommand())
         .subcommand(import_subcommand())
+        .subcommand(passwd_subcommand())
+        .subcommand(backups_subcommand())
+        .subcommand(generate_subcommand())
         .subcommand(check_reminders_subcommand())
+        .subcommand(run_subcommand())
+        .subcommand(inject_subcommand())
+        .subcommand(tui_subcommand())
+        .subcommand(agent_subcommand())
+        .subcommand(lock_subcommand())
+        .subcommand(keyfile_subcommand())
+        .subcommand(recovery_subcommand())
+        .subcommand(clipboard_guard_subcommand())
 }
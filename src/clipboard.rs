@@ -0,0 +1,100 @@
+use anyhow::{anyhow, Result};
+use arboard::Clipboard;
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Hidden subcommand the detached helper runs under.
+pub const GUARD_COMMAND: &str = "clipboard-guard";
+
+/// Seconds before copied secrets are cleared, from `DPLOCK_CLIPBOARD_TIMEOUT` (0 disables).
+pub fn clear_timeout() -> u64 {
+    env::var("DPLOCK_CLIPBOARD_TIMEOUT")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(30) // Default to 30 seconds
+}
+
+/// Copies `text` and returns the number of seconds until it is cleared, if it will be.
+///
+/// When clearing is wanted, or on Linux where clipboard content disappears with the process
+/// that owns it, a detached copy of dplock takes ownership of the clipboard instead.
+pub fn copy(text: &str, no_clear: bool) -> Result<Option<u64>> {
+    let timeout = if no_clear { 0 } else { clear_timeout() };
+
+    if timeout == 0 && !cfg!(target_os = "linux") {
+        let mut clipboard = Clipboard::new().map_err(|e| anyhow!("Clipboard error: {e}"))?;
+        clipboard
+            .set_text(text.to_string())
+            .map_err(|e| anyhow!("Failed to copy to clipboard: {e}"))?;
+        return Ok(None);
+    }
+
+    spawn_guard(text, timeout)?;
+    Ok((timeout > 0).then_some(timeout))
+}
+
+fn spawn_guard(text: &str, timeout: u64) -> Result<()> {
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .arg(GUARD_COMMAND)
+        .arg(timeout.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    {
+        // Own process group, so closing the terminal doesn't take the helper down with it.
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command.spawn()?;
+
+    // The secret goes through a pipe, never through argv or the environment.
+    {
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("Clipboard helper has no stdin"))?;
+        stdin.write_all(text.as_bytes())?;
+    }
+
+    let stdout = child.stdout.take().ok_or_else(|| anyhow!("Clipboard helper has no stdout"))?;
+    let mut status = String::new();
+    BufReader::new(stdout).read_line(&mut status)?;
+    match status.trim() {
+        "ok" => Ok(()),
+        "" => Err(anyhow!("Clipboard error: helper exited unexpectedly")),
+        message => Err(anyhow!("Clipboard error: {}", message.trim_start_matches("error: "))),
+    }
+}
+
+/// Entry point of the detached helper: reads the secret from stdin, puts it on the clipboard,
+/// and keeps owning it until `timeout` seconds pass (0 = until something else is copied).
+/// It only clears the clipboard if it still holds our value.
+pub fn run_guard(timeout: u64) -> Result<()> {
+    let mut secret = String::new();
+    io::stdin().read_to_string(&mut secret)?;
+
+    let mut clipboard = match Clipboard::new().and_then(|mut c| c.set_text(secret.clone()).map(|_| c)) {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            println!("error: {e}");
+            return Ok(());
+        }
+    };
+    println!("ok");
+    io::stdout().flush()?;
+
+    let deadline = (timeout > 0).then(|| Instant::now() + Duration::from_secs(timeout));
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        let still_ours = clipboard.get_text().map(|text| text == secret).unwrap_or(false);
+        if !still_ours {
+            return Ok(());
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            let _ = clipboard.clear();
+            return Ok(());
+        }
+    }
+}
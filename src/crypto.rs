@@ -1,67 +1,465 @@
-use anyhow::{anyhow, Result};
-use argon2::{Argon2, PasswordHasher};
-use argon2::password_hash::SaltString;
-use base64::Engine;
-use base64::engine::general_purpose::STANDARD_NO_PAD;
-use chacha20poly1305::aead::{Aead, KeyInit};
+use crate::error::{Error, Result};
+use crate::keyfile::Keyfile;
+use crate::recovery::RecoveryKey;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
 use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
 use rand::{rngs::OsRng, RngCore};
 use serde::{Serialize, de::DeserializeOwned};
-use bincode::{serialize, deserialize};
+use bincode::deserialize;
+use base64::Engine;
 use base64::{engine::general_purpose};
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8; 4] = b"DPLK";
+// 1: bincode payload, 2: JSON payload (lets structs gain `#[serde(default)]` fields),
+// 3: JSON payload with flags in the high bits of the variant byte, 4: as 3, followed by the
+// wrapped payload key (see `WrappedKeys`). Only vaults that need it are written as 3 or 4, so
+// the others stay readable by older versions.
+const FORMAT_VERSION: u8 = 4;
+const FLAG_KEYFILE: u8 = 0x80;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+// magic | version | variant | m_cost | t_cost | p_cost | salt
+const HEADER_LEN: usize = 4 + 1 + 1 + 4 + 4 + 4 + SALT_LEN;
+// nonce | key | tag
+const WRAPPED_LEN: usize = NONCE_LEN + 32 + 16;
+const WRAPPED_KEYS_LEN: usize = 2 * WRAPPED_LEN;
+// Upper bounds for Argon2 costs read from a header; anything above is a damaged or hostile file,
+// and deriving with it could exhaust memory or run for hours.
+const MAX_M_COST: u32 = 4 * 1024 * 1024; // KiB, i.e. 4 GiB
+const MAX_T_COST: u32 = 64;
+const MAX_P_COST: u32 = 64;
+const PASSWORD_SLOT: &[u8] = b"dplock password key";
+const RECOVERY_SLOT: &[u8] = b"dplock recovery key";
+
+/// Argon2 cost parameters used to derive the vault key, and whether a keyfile goes into it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub variant: Algorithm,
+    pub keyfile: bool,
+}
+
+impl Default for KdfParams {
+    /// Matches `Argon2::default()`, which is what headerless vaults were written with.
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+            variant: Algorithm::Argon2id,
+            keyfile: false,
+        }
+    }
+}
+
+fn variant_to_byte(variant: Algorithm) -> u8 {
+    match variant {
+        Algorithm::Argon2d => 0,
+        Algorithm::Argon2i => 1,
+        Algorithm::Argon2id => 2,
+    }
+}
+
+fn variant_from_byte(byte: u8) -> Result<Algorithm> {
+    match byte {
+        0 => Ok(Algorithm::Argon2d),
+        1 => Ok(Algorithm::Argon2i),
+        2 => Ok(Algorithm::Argon2id),
+        _ => Err(Error::CorruptVault(format!("unknown Argon2 variant {byte}"))),
+    }
+}
+
+fn write_header(params: &KdfParams, salt: &[u8]) -> Vec<u8> {
+    write_header_version(params, salt, if params.keyfile { 3 } else { 2 })
+}
+
+fn write_header_version(params: &KdfParams, salt: &[u8], version: u8) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend(MAGIC);
+    header.push(version);
+    if params.keyfile {
+        header.push(variant_to_byte(params.variant) | FLAG_KEYFILE);
+    } else {
+        header.push(variant_to_byte(params.variant));
+    }
+    header.extend(params.m_cost.to_le_bytes());
+    header.extend(params.t_cost.to_le_bytes());
+    header.extend(params.p_cost.to_le_bytes());
+    header.extend(salt);
+    header
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+fn read_header(bytes: &[u8]) -> Result<KdfParams> {
+    let flags = if bytes[MAGIC.len()] >= 3 { bytes[5] & FLAG_KEYFILE } else { 0 };
+    let params = KdfParams {
+        variant: variant_from_byte(bytes[5] & !flags)?,
+        keyfile: flags & FLAG_KEYFILE != 0,
+        m_cost: read_u32(bytes, 6),
+        t_cost: read_u32(bytes, 10),
+        p_cost: read_u32(bytes, 14),
+    };
+    if params.m_cost > MAX_M_COST || params.t_cost > MAX_T_COST || params.p_cost > MAX_P_COST {
+        return Err(Error::CorruptVault(format!(
+            "KDF parameters out of range (m={}, t={}, p={})",
+            params.m_cost, params.t_cost, params.p_cost
+        )));
+    }
+    Ok(params)
+}
+
+/// Length of the authenticated header of a vault in format `version`.
+fn header_len(version: u8) -> usize {
+    if version >= 4 { HEADER_LEN + WRAPPED_KEYS_LEN } else { HEADER_LEN }
+}
+
+/// The payload key of a format 4 vault, encrypted once with the key derived from the master
+/// password and once with the recovery key. Each is `nonce || ciphertext`.
+#[derive(Clone, PartialEq, Eq)]
+struct WrappedKeys {
+    password: [u8; WRAPPED_LEN],
+    recovery: [u8; WRAPPED_LEN],
+}
+
+/// How the key of a vault is made: the salt and parameters to derive it with and, from format
+/// 4 on, the payload key wrapped by the derived one.
+#[derive(Clone, PartialEq, Eq)]
+struct KeyHeader {
+    salt: [u8; SALT_LEN],
+    params: KdfParams,
+    wrapped: Option<WrappedKeys>,
+}
+
+impl KeyHeader {
+    fn fresh(keyfile: bool) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self { salt, params: KdfParams { keyfile, ..KdfParams::default() }, wrapped: None }
+    }
+
+    /// The header as written: format 4 with wrapped keys, else 3 or 2 (see `FORMAT_VERSION`).
+    fn to_bytes(&self) -> Vec<u8> {
+        let Some(wrapped) = &self.wrapped else {
+            return write_header(&self.params, &self.salt);
+        };
+        let mut header = write_header_version(&self.params, &self.salt, FORMAT_VERSION);
+        header.extend(wrapped.password);
+        header.extend(wrapped.recovery);
+        header
+    }
+
+    /// Reads the header of the vault `bytes`; `len` is how many bytes it takes (0 for headerless
+    /// vaults).
+    fn parse(bytes: &[u8]) -> Result<(Self, usize)> {
+        let mut salt = [0u8; SALT_LEN];
+        if !bytes.starts_with(MAGIC) {
+            if bytes.len() < SALT_LEN + NONCE_LEN {
+                return Err(too_short());
+            }
+            salt.copy_from_slice(&bytes[..SALT_LEN]);
+            return Ok((Self { salt, params: KdfParams::default(), wrapped: None }, 0));
+        }
+        let version = *bytes.get(MAGIC.len()).ok_or_else(too_short)?;
+        if !(1..=FORMAT_VERSION).contains(&version) {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let len = header_len(version);
+        if bytes.len() < len {
+            return Err(too_short());
+        }
+        salt.copy_from_slice(&bytes[HEADER_LEN - SALT_LEN..HEADER_LEN]);
+        let wrapped = (version >= 4).then(|| {
+            let mut wrapped = WrappedKeys { password: [0u8; WRAPPED_LEN], recovery: [0u8; WRAPPED_LEN] };
+            wrapped.password.copy_from_slice(&bytes[HEADER_LEN..HEADER_LEN + WRAPPED_LEN]);
+            wrapped.recovery.copy_from_slice(&bytes[HEADER_LEN + WRAPPED_LEN..len]);
+            wrapped
+        });
+        Ok((Self { salt, params: read_header(&bytes[..HEADER_LEN])?, wrapped }, len))
+    }
+
+    fn derive(&self, password: &str, keyfile: Option<&Keyfile>) -> Result<Zeroizing<[u8; 32]>> {
+        let key = derive_key(password, keyfile, &self.salt, &self.params)?;
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        bytes.copy_from_slice(&key);
+        Ok(bytes)
+    }
+}
+
+/// Encrypts `key` with `wrapping_key`; `slot` tells the two wrapped keys apart.
+fn wrap(wrapping_key: &[u8; 32], key: &[u8; 32], slot: &[u8]) -> Result<[u8; WRAPPED_LEN]> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(wrapping_key))
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: key, aad: slot })
+        .map_err(|e| Error::other(format!("Encryption failed: {e}")))?;
+    let mut wrapped = [0u8; WRAPPED_LEN];
+    wrapped[..NONCE_LEN].copy_from_slice(&nonce);
+    wrapped[NONCE_LEN..].copy_from_slice(&ciphertext);
+    Ok(wrapped)
+}
+
+fn unwrap(wrapping_key: &[u8; 32], wrapped: &[u8; WRAPPED_LEN], slot: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    let (nonce, ciphertext) = wrapped.split_at(NONCE_LEN);
+    let plaintext = Zeroizing::new(ChaCha20Poly1305::new(Key::from_slice(wrapping_key))
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: slot })
+        .map_err(|_| Error::WrongPassword)?);
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&plaintext);
+    Ok(key)
+}
+
+/// The key a vault's payload is encrypted with, and the header it belongs to. Up to format 3
+/// it's derived from the master password; from format 4 on it's random and wrapped by the
+/// derived key and by a recovery key. Saving a vault with it keeps the header, so the key
+/// stays valid until the master password changes.
+#[derive(Clone)]
+pub struct VaultKey {
+    key: Zeroizing<[u8; 32]>,
+    header: KeyHeader,
+}
+
+impl VaultKey {
+    /// A key with a fresh salt, for a new vault or a new master password.
+    /// With a `keyfile`, the vault can only be opened with both.
+    pub fn generate(password: &str, keyfile: Option<&Keyfile>) -> Result<Self> {
+        let header = KeyHeader::fresh(keyfile.is_some());
+        Ok(Self { key: header.derive(password, keyfile)?, header })
+    }
+
+    /// The key for the encrypted vault `bytes`, derived with the salt and parameters stored in it.
+    /// `keyfile` is only used if the vault requires one.
+    pub fn for_vault(bytes: &[u8], password: &str, keyfile: Option<&Keyfile>) -> Result<Self> {
+        let (header, _) = KeyHeader::parse(bytes)?;
+        let derived = header.derive(password, keyfile)?;
+        let key = match &header.wrapped {
+            Some(wrapped) => unwrap(&derived, &wrapped.password, PASSWORD_SLOT)?,
+            None => derived,
+        };
+        Ok(Self { key, header })
+    }
+
+    /// The key for the encrypted vault `bytes`, unwrapped with its recovery key.
+    pub fn for_recovery(bytes: &[u8], recovery: &RecoveryKey) -> Result<Self> {
+        let (header, _) = KeyHeader::parse(bytes)?;
+        let wrapped = header.wrapped.as_ref().ok_or_else(Error::no_recovery)?;
+        let key = unwrap(recovery.as_bytes(), &wrapped.recovery, RECOVERY_SLOT)?;
+        Ok(Self { key, header })
+    }
+
+    /// The same vault key with a new master password: a fresh salt, and the keyfile
+    /// requirement set by `keyfile`. Keys without a recovery key are replaced by a new one.
+    pub fn with_password(&self, password: &str, keyfile: Option<&Keyfile>) -> Result<Self> {
+        let Some(wrapped) = &self.header.wrapped else {
+            return Self::generate(password, keyfile);
+        };
+        let mut header = KeyHeader::fresh(keyfile.is_some());
+        let derived = header.derive(password, keyfile)?;
+        header.wrapped = Some(WrappedKeys {
+            password: wrap(&derived, &self.key, PASSWORD_SLOT)?,
+            recovery: wrapped.recovery,
+        });
+        Ok(Self { key: self.key.clone(), header })
+    }
+
+    /// A key that `recovery` can also unwrap, replacing an earlier recovery key. A key
+    /// derived from the password becomes the wrapping key of a new random vault key, so the
+    /// vault has to be saved with the result.
+    pub fn with_recovery(&self, recovery: &RecoveryKey) -> Result<Self> {
+        let (key, password) = match &self.header.wrapped {
+            Some(wrapped) => (self.key.clone(), wrapped.password),
+            None => {
+                let mut key = Zeroizing::new([0u8; 32]);
+                OsRng.fill_bytes(key.as_mut_slice());
+                let password = wrap(&self.key, &key, PASSWORD_SLOT)?;
+                (key, password)
+            }
+        };
+        let recovery = wrap(recovery.as_bytes(), &key, RECOVERY_SLOT)?;
+        let header = KeyHeader { wrapped: Some(WrappedKeys { password, recovery }), ..self.header.clone() };
+        Ok(Self { key, header })
+    }
+
+    pub fn has_recovery(&self) -> bool {
+        self.header.wrapped.is_some()
+    }
+
+    /// Key, then the vault header it belongs to; read back with `from_bytes`.
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut bytes = Zeroizing::new(self.key.to_vec());
+        bytes.extend(self.header.to_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 32 || !bytes[32..].starts_with(MAGIC) {
+            return Err(Error::other("Invalid key"));
+        }
+        let (key_bytes, header) = bytes.split_at(32);
+        let (header, len) = KeyHeader::parse(header).map_err(|_| Error::other("Invalid key"))?;
+        if bytes.len() != 32 + len {
+            return Err(Error::other("Invalid key"));
+        }
+        let mut key = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(key_bytes);
+        Ok(Self { key, header })
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(self.key.as_slice()))
+    }
+}
+
+fn too_short() -> Error {
+    Error::CorruptVault("the file is too short".to_string())
+}
+
+fn corrupt(e: impl std::fmt::Display) -> Error {
+    Error::CorruptVault(e.to_string())
+}
+
+/// Whether the vault `bytes` can only be opened with a keyfile.
+pub fn requires_keyfile(bytes: &[u8]) -> bool {
+    KeyHeader::parse(bytes).is_ok_and(|(header, _)| header.params.keyfile)
+}
+
+/// Whether the vault `bytes` have a recovery key (format 4).
+pub fn has_recovery(bytes: &[u8]) -> bool {
+    KeyHeader::parse(bytes).is_ok_and(|(header, _)| header.wrapped.is_some())
+}
+
+/// Encrypts a vault payload (JSON, see `FORMAT_VERSION`) with `key`, keeping its header.
+pub fn encrypt_with_key(payload: &[u8], key: &VaultKey) -> Result<Vec<u8>> {
+    let header = key.header.to_bytes();
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let ciphertext = key.cipher()
+        .encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: payload, aad: &header })
+        .map_err(|e| Error::other(format!("Encryption failed: {e}")))?;
+
+    let mut result = header;
+    result.extend(&nonce_bytes);
+    result.extend(ciphertext);
+    Ok(result)
+}
+
+/// Decrypts the vault `bytes` with `key` and returns the raw payload; `is_bincode_payload`
+/// tells how to decode it. Fails if the vault was written with another header since.
+pub fn decrypt_with_key(bytes: &[u8], key: &VaultKey) -> Result<Zeroizing<Vec<u8>>> {
+    let (header, len) = KeyHeader::parse(bytes)?;
+    if header != key.header {
+        return Err(Error::WrongPassword);
+    }
+    let plaintext = if bytes.starts_with(MAGIC) {
+        if bytes.len() < len + NONCE_LEN {
+            return Err(too_short());
+        }
+        let (header, rest) = bytes.split_at(len);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        key.cipher().decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+    } else {
+        let nonce = &bytes[SALT_LEN..SALT_LEN + NONCE_LEN];
+        key.cipher().decrypt(Nonce::from_slice(nonce), &bytes[SALT_LEN + NONCE_LEN..])
+    };
+    plaintext.map(Zeroizing::new).map_err(|_| Error::WrongPassword)
+}
 
 pub fn encrypt<T: Serialize>(data: &T, password: &str) -> Result<Vec<u8>> {
-    let serialized = serialize(data)?;
-    let mut salt = [0u8; 16];
+    let serialized = serde_json::to_vec(data).map_err(Error::other)?;
+    let params = KdfParams::default();
+    let mut salt = [0u8; SALT_LEN];
     OsRng.fill_bytes(&mut salt);
-    let key = derive_key(password, &salt)?;
+    let key = derive_key(password, None, &salt, &params)?;
     let cipher = ChaCha20Poly1305::new(&key);
 
-    let mut nonce_bytes = [0u8; 12];
+    let header = write_header(&params, &salt);
+    let mut nonce_bytes = [0u8; NONCE_LEN];
     OsRng.fill_bytes(&mut nonce_bytes);
     let nonce = Nonce::from_slice(&nonce_bytes);
-    let ciphertext = cipher.encrypt(nonce, serialized.as_ref())
-        .map_err(|e| anyhow!("Encryption failed: {e}"))?;
+    let payload = Payload { msg: serialized.as_ref(), aad: &header };
+    let ciphertext = cipher.encrypt(nonce, payload)
+        .map_err(|e| Error::other(format!("Encryption failed: {e}")))?;
 
-    let mut result = vec![];
-    result.extend(&salt);
+    let mut result = header;
     result.extend(&nonce_bytes);
     result.extend(ciphertext);
     Ok(result)
 }
 
 pub fn decrypt<T: DeserializeOwned>(bytes: &[u8], password: &str) -> Result<T> {
-    if bytes.len() < 16 + 12 {
-        return Err(anyhow!("Invalid data"));
+    if !bytes.starts_with(MAGIC) {
+        return decrypt_legacy(bytes, password);
+    }
+    if bytes.len() < MAGIC.len() + 1 {
+        return Err(too_short());
+    }
+    match bytes[MAGIC.len()] {
+        1 => deserialize(&decrypt_with_header(bytes, password)?).map_err(corrupt),
+        2 | 3 => serde_json::from_slice(&decrypt_with_header(bytes, password)?).map_err(corrupt),
+        version => Err(Error::UnsupportedVersion(version)),
     }
-    let salt = &bytes[..16];
-    let nonce = &bytes[16..28];
-    let ciphertext = &bytes[28..];
+}
+
+/// Whether `bytes` carry a bincode payload (headerless or format version 1). Bincode is not
+/// self-describing, so such payloads must be read with the struct layout they were written with.
+pub fn is_bincode_payload(bytes: &[u8]) -> bool {
+    !bytes.starts_with(MAGIC) || bytes.get(MAGIC.len()) == Some(&1)
+}
 
-    let key = derive_key(password, salt)?;
+fn decrypt_with_header(bytes: &[u8], password: &str) -> Result<Vec<u8>> {
+    if bytes.len() < HEADER_LEN + NONCE_LEN {
+        return Err(too_short());
+    }
+    let (header, rest) = bytes.split_at(HEADER_LEN);
+    let params = read_header(header)?;
+    let salt = &header[HEADER_LEN - SALT_LEN..];
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let key = derive_key(password, None, salt, &params)?;
     let cipher = ChaCha20Poly1305::new(&key);
-    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
-        .map_err(|e| anyhow!("Decryption failed: {e}"))?;
-    Ok(deserialize(&plaintext)?)
+    let payload = Payload { msg: ciphertext, aad: header };
+    cipher.decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| Error::WrongPassword)
 }
 
-fn derive_key(password: &str, salt: &[u8]) -> Result<Key> {
-    let salt_b64 = STANDARD_NO_PAD.encode(salt);
-    let salt_str = SaltString::from_b64(&salt_b64)
-        .map_err(|e| anyhow!("Invalid salt: {e}"))?;
+/// Headerless `salt || nonce || ciphertext` blobs written before the format was versioned.
+fn decrypt_legacy<T: DeserializeOwned>(bytes: &[u8], password: &str) -> Result<T> {
+    if bytes.len() < SALT_LEN + NONCE_LEN {
+        return Err(too_short());
+    }
+    let salt = &bytes[..SALT_LEN];
+    let nonce = &bytes[SALT_LEN..SALT_LEN + NONCE_LEN];
+    let ciphertext = &bytes[SALT_LEN + NONCE_LEN..];
 
-    let argon2 = Argon2::default();
+    let key = derive_key(password, None, salt, &KdfParams::default())?;
+    let cipher = ChaCha20Poly1305::new(&key);
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::WrongPassword)?;
+    deserialize(&plaintext).map_err(corrupt)
+}
 
-    let password_hash = argon2
-        .hash_password(password.as_bytes(), &salt_str)
-        .map_err(|e| anyhow!("Hashing failed: {}", e))?
-        .hash
-        .ok_or_else(|| anyhow!("Hash missing"))?;
+fn derive_key(password: &str, keyfile: Option<&Keyfile>, salt: &[u8], params: &KdfParams) -> Result<Key> {
+    let argon2_params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+        .map_err(|e| Error::CorruptVault(format!("invalid KDF parameters: {e}")))?;
+    let argon2 = match (params.keyfile, keyfile) {
+        (false, _) => Argon2::new(params.variant, Version::V0x13, argon2_params),
+        (true, Some(keyfile)) => Argon2::new_with_secret(keyfile.secret(), params.variant, Version::V0x13, argon2_params)
+            .map_err(|e| Error::other(format!("Hashing failed: {}", e)))?,
+        (true, None) => return Err(Error::keyfile_required()),
+    };
 
-    let key_bytes = password_hash.as_bytes();
     let mut key = [0u8; 32];
-    let copy_len = key_bytes.len().min(32);
-    key[..copy_len].copy_from_slice(&key_bytes[..copy_len]);
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::other(format!("Hashing failed: {}", e)))?;
 
     Ok(*Key::from_slice(&key))
 }
@@ -72,7 +470,88 @@ pub fn encrypt_entry(password: &str, master: &str) -> Result<String> {
 }
 
 pub fn decrypt_entry(encoded: &str, master: &str) -> Result<String> {
-    let bytes = general_purpose::STANDARD.decode(encoded)?;
+    let bytes = general_purpose::STANDARD.decode(encoded).map_err(corrupt)?;
     let decrypted: String = decrypt(&bytes, master)?;
     Ok(decrypted)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `"hunter2"` as a headerless entry (bincode payload, default Argon2 parameters) under the
+    /// password `correct horse`, the format written before the header was versioned.
+    const LEGACY_ENTRY: &str = "ZHBsb2NrLXRlc3Qtc2FsdGxlZ2FjeS1ub25jZRYA58RcdfEIJi054TZzbJIqEputnc/62Ceq1vkLops=";
+
+    #[test]
+    fn header_round_trip() {
+        for keyfile in [false, true] {
+            let header = KeyHeader::fresh(keyfile);
+            let bytes = header.to_bytes();
+            assert_eq!(bytes[MAGIC.len()], if keyfile { 3 } else { 2 });
+            let (parsed, len) = KeyHeader::parse(&bytes).unwrap();
+            assert!(parsed == header);
+            assert_eq!(len, HEADER_LEN);
+        }
+
+        let header = KeyHeader {
+            wrapped: Some(WrappedKeys { password: [1; WRAPPED_LEN], recovery: [2; WRAPPED_LEN] }),
+            ..KeyHeader::fresh(true)
+        };
+        let bytes = header.to_bytes();
+        assert_eq!(bytes[MAGIC.len()], FORMAT_VERSION);
+        let (parsed, len) = KeyHeader::parse(&bytes).unwrap();
+        assert!(parsed == header);
+        assert_eq!(len, HEADER_LEN + WRAPPED_KEYS_LEN);
+        assert!(requires_keyfile(&bytes) && has_recovery(&bytes));
+    }
+
+    #[test]
+    fn header_rejects_newer_and_short_files() {
+        let mut bytes = KeyHeader::fresh(false).to_bytes();
+        bytes[MAGIC.len()] = FORMAT_VERSION + 1;
+        assert!(matches!(KeyHeader::parse(&bytes), Err(Error::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1));
+        assert!(matches!(KeyHeader::parse(b"DPLK"), Err(Error::CorruptVault(_))));
+    }
+
+    #[test]
+    fn header_rejects_excessive_kdf_costs() {
+        for offset in [6, 10, 14] {
+            let mut bytes = KeyHeader::fresh(false).to_bytes();
+            bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            assert!(matches!(KeyHeader::parse(&bytes), Err(Error::CorruptVault(_))));
+        }
+    }
+
+    #[test]
+    fn legacy_vector() {
+        assert_eq!(decrypt_entry(LEGACY_ENTRY, "correct horse").unwrap(), "hunter2");
+        assert!(matches!(decrypt_entry(LEGACY_ENTRY, "wrong horse"), Err(Error::WrongPassword)));
+    }
+
+    #[test]
+    fn wrap_unwrap() {
+        let (wrapping_key, key) = ([7u8; 32], [9u8; 32]);
+        let wrapped = wrap(&wrapping_key, &key, PASSWORD_SLOT).unwrap();
+        assert_eq!(*unwrap(&wrapping_key, &wrapped, PASSWORD_SLOT).unwrap(), key);
+        assert!(matches!(unwrap(&wrapping_key, &wrapped, RECOVERY_SLOT), Err(Error::WrongPassword)));
+        assert!(matches!(unwrap(&[8u8; 32], &wrapped, PASSWORD_SLOT), Err(Error::WrongPassword)));
+    }
+
+    #[test]
+    fn vault_key_opens_with_password_and_recovery() {
+        let recovery = RecoveryKey::generate();
+        let key = VaultKey::generate("master", None).unwrap().with_recovery(&recovery).unwrap();
+        let bytes = encrypt_with_key(b"payload", &key).unwrap();
+
+        let by_password = VaultKey::for_vault(&bytes, "master", None).unwrap();
+        assert_eq!(&*decrypt_with_key(&bytes, &by_password).unwrap(), b"payload");
+        let by_recovery = VaultKey::for_recovery(&bytes, &recovery).unwrap();
+        assert_eq!(&*decrypt_with_key(&bytes, &by_recovery).unwrap(), b"payload");
+
+        assert!(matches!(VaultKey::for_vault(&bytes, "other", None), Err(Error::WrongPassword)));
+        assert!(matches!(VaultKey::for_recovery(&bytes, &RecoveryKey::generate()), Err(Error::WrongPassword)));
+        let restored = VaultKey::from_bytes(&key.to_bytes()).unwrap();
+        assert_eq!(&*decrypt_with_key(&bytes, &restored).unwrap(), b"payload");
+    }
+}
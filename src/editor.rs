@@ -0,0 +1,81 @@
+use anyhow::{anyhow, Result};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A temporary file holding decrypted data. On drop it is overwritten with zeros and removed.
+struct SecretTempFile {
+    path: PathBuf,
+}
+
+impl SecretTempFile {
+    fn create(contents: &str) -> Result<Self> {
+        let name: String = rand::thread_rng().sample_iter(&Alphanumeric).take(12).map(char::from).collect();
+        let path = ram_dir().join(format!("dplock-edit-{}.json", name));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        let temp = Self { path };
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        Ok(temp)
+    }
+}
+
+impl Drop for SecretTempFile {
+    fn drop(&mut self) {
+        // Editors may have replaced the file, so wipe whatever is at the path now.
+        if let Ok(mut file) = OpenOptions::new().write(true).open(&self.path) {
+            let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+            let _ = file.seek(SeekFrom::Start(0));
+            let _ = file.write_all(&vec![0u8; len as usize]);
+            let _ = file.sync_all();
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Prefers memory-backed directories so decrypted data never reaches the disk.
+fn ram_dir() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    if shm.is_dir() {
+        return shm.to_path_buf();
+    }
+    if let Some(runtime) = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).filter(|p| p.is_dir()) {
+        return runtime;
+    }
+    eprintln!("⚠️  No RAM-backed directory found; the temporary file is written to {}.", env::temp_dir().display());
+    env::temp_dir()
+}
+
+/// Opens `contents` in `$VISUAL`/`$EDITOR` (default: vi) and returns the edited text.
+pub fn edit(contents: &str) -> Result<String> {
+    let temp = SecretTempFile::create(contents)?;
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or_else(|| anyhow!("❌ $EDITOR is empty."))?;
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(&temp.path)
+        .status()
+        .map_err(|e| anyhow!("❌ Failed to start editor '{}': {e}", editor))?;
+    if !status.success() {
+        return Err(anyhow!("❌ Editor exited with {}; nothing was changed.", status));
+    }
+
+    Ok(fs::read_to_string(&temp.path)?)
+}
@@ -0,0 +1,77 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors of the library API. Messages are plain text; the CLI decorates them.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("No vault at {}. Create one with: dplock init", .0.display())]
+    VaultNotFound(PathBuf),
+    /// The password doesn't open the vault.
+    #[error("Incorrect master password")]
+    WrongPassword,
+    /// The file is not a vault, or is damaged.
+    #[error("The vault cannot be read: {0}")]
+    CorruptVault(String),
+    /// The vault was written by a newer dplock.
+    #[error("Unsupported vault format version: {0}. Upgrade dplock to open it")]
+    UnsupportedVersion(u8),
+    /// The agent no longer holds the key, e.g. after its idle timeout.
+    #[error("The vault is locked. Unlock it again with the master password")]
+    Locked,
+    /// The vault requires a keyfile and none was given, or it cannot be read.
+    #[error("{0}")]
+    Keyfile(String),
+    /// The vault has no recovery kit, or the shares given don't restore its key.
+    #[error("{0}")]
+    Recovery(String),
+    /// The OS keyring could not be reached.
+    #[error("Keyring error: {0}")]
+    Keyring(String),
+    /// Input that doesn't have the expected format, such as a duration or an entry name.
+    #[error("{0}")]
+    Parse(String),
+    #[error("{}", not_found(name, username.as_deref()))]
+    EntryNotFound { name: String, username: Option<String> },
+    /// More than one entry matches; narrow it down by username or index.
+    #[error("Several entries match under '{name}': {}. Specify a username", .usernames.join(", "))]
+    AmbiguousEntry { name: String, usernames: Vec<String> },
+    /// The change would overwrite entries, or the vault changed underneath it.
+    #[error("{0}")]
+    Conflict(String),
+    /// Another process held the vault lock for longer than the lock timeout.
+    #[error("Vault is in use by another dplock process (lock: {}). Try again later", .0.display())]
+    Busy(PathBuf),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// Failures of the layers below, such as locking the vault file or talking to the agent.
+    #[error("{0}")]
+    Other(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn other(error: impl fmt::Display) -> Self {
+        Error::Other(error.to_string())
+    }
+
+    pub(crate) fn keyfile_required() -> Self {
+        Error::Keyfile("This vault requires a keyfile. Pass it with --keyfile or DPLOCK_KEYFILE".to_string())
+    }
+
+    pub(crate) fn no_recovery() -> Self {
+        Error::Recovery("This vault has no recovery kit. Create one with: dplock recovery create".to_string())
+    }
+
+    pub(crate) fn parse(error: impl fmt::Display) -> Self {
+        Error::Parse(error.to_string())
+    }
+}
+
+fn not_found(name: &str, username: Option<&str>) -> String {
+    match username {
+        None => format!("No entries found with name: '{}'", name),
+        Some(username) => format!("No entries found matching username '{}' under '{}'", username, name),
+    }
+}
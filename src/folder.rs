@@ -0,0 +1,114 @@
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+
+/// Cleans up a slash-separated entry name such as `work/aws/prod`: surrounding slashes and
+/// whitespace around segments are dropped, empty segments are rejected.
+pub fn normalize_name(name: &str) -> Result<String> {
+    let trimmed = name.trim().trim_matches('/');
+    let segments: Vec<&str> = trimmed.split('/').map(str::trim).collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(Error::Parse(format!("Invalid entry name '{}'. Use e.g: github or work/aws/prod", name)));
+    }
+    Ok(segments.join("/"))
+}
+
+/// Whether `name` is `folder` itself or lies anywhere below it.
+pub fn contains(folder: &str, name: &str) -> bool {
+    let folder = folder.trim_matches('/');
+    folder.is_empty()
+        || name == folder
+        || name.strip_prefix(folder).is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[derive(Default)]
+struct Node<'a> {
+    labels: Vec<String>,
+    children: BTreeMap<&'a str, Node<'a>>,
+}
+
+/// Lays out `(name, label)` pairs as a folder tree, one line per label under the last segment
+/// of its name.
+pub fn tree_lines(items: &[(&str, String)]) -> Vec<String> {
+    let mut root = Node::default();
+    for (name, label) in items {
+        let mut node = &mut root;
+        for segment in name.split('/') {
+            node = node.children.entry(segment).or_default();
+        }
+        node.labels.push(label.clone());
+    }
+    let mut lines = Vec::new();
+    push_children(&root, "", &mut lines);
+    lines
+}
+
+fn push_children(node: &Node, prefix: &str, lines: &mut Vec<String>) {
+    let count = node.children.len();
+    for (i, (segment, child)) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        let child_prefix = format!("{}{}", prefix, indent);
+
+        if child.children.is_empty() && child.labels.len() == 1 {
+            lines.push(format!("{}{}🔑 {} {}", prefix, branch, segment, child.labels[0]));
+            continue;
+        }
+        if child.children.is_empty() {
+            lines.push(format!("{}{}🔑 {}", prefix, branch, segment));
+        } else {
+            lines.push(format!("{}{}📁 {}", prefix, branch, segment));
+        }
+        for label in &child.labels {
+            lines.push(format!("{}🔑 {}", child_prefix, label));
+        }
+        push_children(child, &child_prefix, lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_trims_slashes_and_segment_whitespace() {
+        assert_eq!(normalize_name("github").unwrap(), "github");
+        assert_eq!(normalize_name("/work/aws/prod/").unwrap(), "work/aws/prod");
+        assert_eq!(normalize_name("  work / aws  ").unwrap(), "work/aws");
+    }
+
+    #[test]
+    fn normalize_rejects_empty_segments() {
+        for name in ["", "/", "  ", "work//aws", "work/ /aws"] {
+            assert!(matches!(normalize_name(name), Err(Error::Parse(_))), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn contains_matches_whole_segments() {
+        assert!(contains("work", "work"));
+        assert!(contains("work", "work/aws/prod"));
+        assert!(contains("work/", "work/aws"));
+        assert!(contains("", "github"));
+        assert!(!contains("work", "workshop"));
+        assert!(!contains("work/aws", "work"));
+    }
+
+    #[test]
+    fn tree_lines_nest_folders_and_group_labels() {
+        let items = [
+            ("work/aws/prod", "(👤 admin)".to_string()),
+            ("github", "(👤 alice)".to_string()),
+            ("work/aws/prod", "(👤 deploy)".to_string()),
+            ("work/mail", "(👤 bob)".to_string()),
+        ];
+        assert_eq!(tree_lines(&items), [
+            "├── 🔑 github (👤 alice)",
+            "└── 📁 work",
+            "    ├── 📁 aws",
+            "    │   └── 🔑 prod",
+            "    │       🔑 (👤 admin)",
+            "    │       🔑 (👤 deploy)",
+            "    └── 🔑 mail (👤 bob)",
+        ]);
+    }
+}
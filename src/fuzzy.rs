@@ -0,0 +1,93 @@
+/// Scores `candidate` against `query` as a case-insensitive subsequence match, or `None` when
+/// the query's characters don't all appear in order. Higher is better: consecutive characters,
+/// matches at word starts (after `/`, `-`, `.`, ...) and an exact or prefix match rank first.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).map(lower).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    let folded: Vec<char> = chars.iter().copied().map(lower).collect();
+
+    // Greedy matching from every occurrence of the first character; keep the best run.
+    let best = (0..folded.len())
+        .filter(|&start| folded[start] == query[0])
+        .filter_map(|start| score_from(&query, &chars, &folded, start))
+        .max()?;
+
+    let bonus = if folded == query {
+        100
+    } else if folded.starts_with(&query) {
+        40
+    } else {
+        0
+    };
+    Some(best + bonus)
+}
+
+fn score_from(query: &[char], chars: &[char], folded: &[char], start: usize) -> Option<i64> {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut pos = start;
+
+    for &q in query {
+        let found = (pos..folded.len()).find(|&i| folded[i] == q)?;
+        score += 16;
+        match previous {
+            Some(prev) if found == prev + 1 => score += 24,
+            Some(prev) => score -= ((found - prev - 1) as i64).min(10),
+            None => score -= (found as i64).min(10),
+        }
+        if is_word_start(chars, found) {
+            score += 20;
+        }
+        previous = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    if i == 0 {
+        return true;
+    }
+    let prev = chars[i - 1];
+    matches!(prev, '/' | '-' | '_' | '.' | ' ' | '@' | ':') || (prev.is_lowercase() && chars[i].is_uppercase())
+}
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_subsequences_case_insensitively() {
+        assert!(score("gh", "github").is_some());
+        assert!(score("GHB", "GitHub").is_some());
+        assert!(score("wap", "work/aws/prod").is_some());
+        assert!(score("aws prod", "work/aws/prod").is_some()); // whitespace in the query is ignored
+        assert_eq!(score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn rejects_out_of_order_or_missing_characters() {
+        assert_eq!(score("bg", "github"), None);
+        assert_eq!(score("gitlab", "github"), None);
+        assert_eq!(score("github", "git"), None);
+    }
+
+    #[test]
+    fn ranks_exact_then_prefix_then_word_starts_then_scattered() {
+        let exact = score("aws", "aws").unwrap();
+        let prefix = score("aws", "aws-prod").unwrap();
+        let word_start = score("aws", "work/aws").unwrap();
+        let scattered = score("aws", "a-big-word-set").unwrap();
+        assert!(exact > prefix, "{exact} > {prefix}");
+        assert!(prefix > word_start, "{prefix} > {word_start}");
+        assert!(word_start > scattered, "{word_start} > {scattered}");
+        assert!(score("gh", "GitHub").unwrap() > score("gh", "foghorn").unwrap());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::error::{Error, Result};
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.?/~";
+const AMBIGUOUS: &str = "Il1O0o|`'\"";
+pub(crate) const WORDLIST: &str = include_str!("../assets/wordlist.txt");
+
+pub struct PasswordPolicy {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub exclude_ambiguous: bool,
+    /// Guarantee at least one character from every enabled class.
+    pub require_each: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+            require_each: true,
+        }
+    }
+}
+
+pub struct PassphrasePolicy {
+    pub words: usize,
+    pub separator: String,
+}
+
+impl Default for PassphrasePolicy {
+    fn default() -> Self {
+        Self {
+            words: 6,
+            separator: "-".to_string(),
+        }
+    }
+}
+
+pub enum Policy {
+    Password(PasswordPolicy),
+    Passphrase(PassphrasePolicy),
+}
+
+pub struct Generated {
+    pub value: String,
+    pub entropy_bits: f64,
+}
+
+impl Policy {
+    /// Parses a compact policy such as `len=24,chars=lud,no-ambiguous` or `words=5,sep=.`.
+    /// An empty string gives the default password policy.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut password = PasswordPolicy::default();
+        let mut passphrase = PassphrasePolicy::default();
+        let mut use_passphrase = false;
+
+        for token in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (key, value) = match token.split_once('=') {
+                Some((k, v)) => (k.trim(), Some(v)),
+                None => (token, None),
+            };
+            match (key, value) {
+                (_, None) if key.chars().all(|c| c.is_ascii_digit()) => {
+                    password.length = parse_count(key)?;
+                }
+                ("len" | "length", Some(v)) => password.length = parse_count(v)?,
+                ("chars", Some(v)) => {
+                    password.lowercase = v.contains('l');
+                    password.uppercase = v.contains('u');
+                    password.digits = v.contains('d');
+                    password.symbols = v.contains('s');
+                }
+                ("no-ambiguous", None) => password.exclude_ambiguous = true,
+                ("no-require", None) => password.require_each = false,
+                ("words", Some(v)) => {
+                    passphrase.words = parse_count(v)?;
+                    use_passphrase = true;
+                }
+                ("passphrase", None) => use_passphrase = true,
+                ("sep", Some(v)) => passphrase.separator = v.to_string(),
+                _ => return Err(Error::Parse(format!("Unknown policy option '{}'. Use e.g: len=24,chars=luds,no-ambiguous or words=6,sep=-", token))),
+            }
+        }
+
+        if use_passphrase {
+            Ok(Policy::Passphrase(passphrase))
+        } else {
+            Ok(Policy::Password(password))
+        }
+    }
+}
+
+fn parse_count(value: &str) -> Result<usize> {
+    value.trim().parse().map_err(|_| Error::Parse(format!("Invalid number in policy: '{}'", value)))
+}
+
+pub fn generate(policy: &Policy) -> Result<Generated> {
+    match policy {
+        Policy::Password(p) => generate_password(p),
+        Policy::Passphrase(p) => generate_passphrase(p),
+    }
+}
+
+fn char_class(chars: &str, exclude_ambiguous: bool) -> Vec<char> {
+    chars.chars()
+        .filter(|c| !exclude_ambiguous || !AMBIGUOUS.contains(*c))
+        .collect()
+}
+
+fn generate_password(policy: &PasswordPolicy) -> Result<Generated> {
+    let classes: Vec<Vec<char>> = [
+        (policy.lowercase, LOWERCASE),
+        (policy.uppercase, UPPERCASE),
+        (policy.digits, DIGITS),
+        (policy.symbols, SYMBOLS),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, chars)| char_class(chars, policy.exclude_ambiguous))
+    .collect();
+
+    if classes.is_empty() {
+        return Err(Error::Parse("At least one character class must be enabled".to_string()));
+    }
+    if policy.length == 0 {
+        return Err(Error::Parse("Password length must be greater than 0".to_string()));
+    }
+    if policy.require_each && policy.length < classes.len() {
+        return Err(Error::Parse(format!("Length {} is too short to include all {} character classes", policy.length, classes.len())));
+    }
+
+    let pool: Vec<char> = classes.iter().flatten().copied().collect();
+    let mut rng = OsRng;
+    let mut chars: Vec<char> = Vec::with_capacity(policy.length);
+
+    if policy.require_each {
+        for class in &classes {
+            chars.push(*class.choose(&mut rng).unwrap());
+        }
+    }
+    while chars.len() < policy.length {
+        chars.push(*pool.choose(&mut rng).unwrap());
+    }
+    chars.shuffle(&mut rng);
+
+    Ok(Generated {
+        value: chars.into_iter().collect(),
+        entropy_bits: policy.length as f64 * (pool.len() as f64).log2(),
+    })
+}
+
+fn generate_passphrase(policy: &PassphrasePolicy) -> Result<Generated> {
+    if policy.words == 0 {
+        return Err(Error::Parse("Passphrase must have at least 1 word".to_string()));
+    }
+
+    let wordlist: Vec<&str> = WORDLIST.lines().filter(|w| !w.is_empty()).collect();
+    let mut rng = OsRng;
+    let words: Vec<&str> = (0..policy.words)
+        .map(|_| *wordlist.choose(&mut rng).unwrap())
+        .collect();
+
+    Ok(Generated {
+        value: words.join(&policy.separator),
+        entropy_bits: policy.words as f64 * (wordlist.len() as f64).log2(),
+    })
+}
+
+pub fn strength_label(entropy_bits: f64) -> &'static str {
+    match entropy_bits {
+        b if b < 40.0 => "weak",
+        b if b < 60.0 => "fair",
+        b if b < 80.0 => "strong",
+        _ => "very strong",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn password(policy: PasswordPolicy) -> String {
+        generate(&Policy::Password(policy)).unwrap().value
+    }
+
+    #[test]
+    fn password_has_length_and_every_class() {
+        for length in [4, 20, 64] {
+            let value = password(PasswordPolicy { length, ..Default::default() });
+            assert_eq!(value.chars().count(), length);
+            for class in [LOWERCASE, UPPERCASE, DIGITS, SYMBOLS] {
+                assert!(value.chars().any(|c| class.contains(c)), "{value} lacks one of {class}");
+            }
+        }
+    }
+
+    #[test]
+    fn password_keeps_to_the_charset() {
+        let policy = PasswordPolicy { length: 200, uppercase: false, symbols: false, ..Default::default() };
+        assert!(password(policy).chars().all(|c| LOWERCASE.contains(c) || DIGITS.contains(c)));
+
+        let policy = PasswordPolicy { length: 200, exclude_ambiguous: true, ..Default::default() };
+        assert!(!password(policy).chars().any(|c| AMBIGUOUS.contains(c)));
+    }
+
+    #[test]
+    fn rejects_impossible_policies() {
+        let none = PasswordPolicy { lowercase: false, uppercase: false, digits: false, symbols: false, ..Default::default() };
+        assert!(matches!(generate(&Policy::Password(none)), Err(Error::Parse(_))));
+        let short = PasswordPolicy { length: 3, ..Default::default() };
+        assert!(matches!(generate(&Policy::Password(short)), Err(Error::Parse(_))));
+        let no_words = PassphrasePolicy { words: 0, ..Default::default() };
+        assert!(matches!(generate(&Policy::Passphrase(no_words)), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn parses_policies() {
+        let Policy::Password(p) = Policy::parse("len=24,chars=lud,no-ambiguous").unwrap() else { panic!("expected a password policy") };
+        assert_eq!(p.length, 24);
+        assert!(p.lowercase && p.uppercase && p.digits && !p.symbols && p.exclude_ambiguous);
+
+        let Policy::Passphrase(p) = Policy::parse("words=5,sep=.").unwrap() else { panic!("expected a passphrase policy") };
+        let generated = generate(&Policy::Passphrase(p)).unwrap();
+        assert_eq!(generated.value.split('.').count(), 5);
+
+        assert!(matches!(Policy::parse("len=x"), Err(Error::Parse(_))));
+        assert!(matches!(Policy::parse("colour=red"), Err(Error::Parse(_))));
+    }
+}
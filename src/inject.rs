@@ -0,0 +1,140 @@
+use crate::reference::SecretRef;
+use dplock::utils::write_secret_file;
+use crate::session::Session;
+use dplock::VaultData;
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+use std::fs;
+use std::path::Path;
+
+/// `{{ dplock "name" ["field" ["username"]] }}`; arguments are double-quoted, `\"` and `\\` escape.
+const PLACEHOLDER: &str = r#"\{\{\s*dplock((?:\s+"(?:[^"\\]|\\.)*")*)\s*\}\}"#;
+const ARGUMENT: &str = r#""((?:[^"\\]|\\.)*)""#;
+
+/// A placeholder that could not be filled in.
+struct Unresolved {
+    line: usize,
+    placeholder: String,
+    error: anyhow::Error,
+}
+
+fn parse_placeholder(caps: &Captures) -> Result<SecretRef> {
+    let args: Vec<String> = Regex::new(ARGUMENT)?
+        .captures_iter(&caps[1])
+        .map(|arg| unescape(&arg[1]))
+        .collect();
+    match args.as_slice() {
+        [name] => Ok(SecretRef { name: name.clone(), field: None, username: None }),
+        [name, field] => Ok(SecretRef { name: name.clone(), field: Some(field.clone()), username: None }),
+        [name, field, username] => Ok(SecretRef { name: name.clone(), field: Some(field.clone()), username: Some(username.clone()) }),
+        _ => Err(anyhow!("❌ Expected 1 to 3 arguments: \"name\" [\"field\" [\"username\"]]")),
+    }
+}
+
+fn unescape(arg: &str) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        out.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+    }
+    out
+}
+
+/// Replaces every placeholder it can resolve and leaves the others as they are.
+fn render(template: &str, data: &VaultData) -> Result<(String, usize, Vec<Unresolved>)> {
+    let placeholder = Regex::new(PLACEHOLDER)?;
+    let mut count = 0;
+    let mut unresolved = Vec::new();
+    let rendered = placeholder.replace_all(template, |caps: &Captures| {
+        count += 1;
+        let whole = caps.get(0).unwrap();
+        match parse_placeholder(caps).and_then(|reference| reference.resolve(data)) {
+            Ok(value) => value,
+            Err(error) => {
+                let line = template[..whole.start()].matches('\n').count() + 1;
+                unresolved.push(Unresolved { line, placeholder: whole.as_str().to_string(), error });
+                whole.as_str().to_string()
+            }
+        }
+    });
+    Ok((rendered.into_owned(), count, unresolved))
+}
+
+/// Renders `input` into `output` (mode 0600). With `check` nothing is written; every reference
+/// is only looked up. With `strict` an unresolved reference fails the whole render.
+pub fn inject(session: &Session, input: &str, output: Option<&str>, strict: bool, check: bool) -> Result<()> {
+    let template = fs::read_to_string(input).map_err(|e| anyhow!("❌ Failed to read template '{}': {e}", input))?;
+
+    let master = session.unlock()?;
+    let data = session.vault().read(&master)?;
+    let (rendered, count, unresolved) = render(&template, &data)?;
+    drop(data);
+
+    for problem in &unresolved {
+        let mark = if check || strict { "❌" } else { "⚠️ " };
+        eprintln!("{} {}:{}: {} — {}", mark, input, problem.line, problem.placeholder, problem.error.to_string().trim_start_matches("❌ "));
+    }
+
+    if check {
+        if !unresolved.is_empty() {
+            return Err(anyhow!("❌ {} of {} reference(s) in '{}' cannot be resolved.", unresolved.len(), count, input));
+        }
+        eprintln!("✅ All {} reference(s) in '{}' resolve.", count, input);
+        return Ok(());
+    }
+    if strict && !unresolved.is_empty() {
+        return Err(anyhow!("❌ {} unresolved reference(s). Nothing was written.", unresolved.len()));
+    }
+
+    let output = output.ok_or_else(|| anyhow!("❌ No output file given. Use -o <PATH>, or --check to only verify."))?;
+    write_secret_file(Path::new(output), rendered.as_bytes())?;
+    if unresolved.is_empty() {
+        eprintln!("✅ Rendered {} reference(s) into: {}", count, output);
+    } else {
+        eprintln!("⚠️  Rendered {} of {} reference(s) into: {} (the others were left as they are; use --strict to fail instead)", count - unresolved.len(), count, output);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dplock::Entry;
+
+    fn placeholder(text: &str) -> Result<SecretRef> {
+        let caps = Regex::new(PLACEHOLDER).unwrap().captures(text).expect("a placeholder");
+        parse_placeholder(&caps)
+    }
+
+    #[test]
+    fn parses_one_to_three_arguments() {
+        let reference = placeholder(r#"{{ dplock "github" }}"#).unwrap();
+        assert_eq!((reference.name.as_str(), reference.field), ("github", None));
+        let reference = placeholder(r#"{{dplock "prod/db" "password" "admin"}}"#).unwrap();
+        assert_eq!(reference.field.as_deref(), Some("password"));
+        assert_eq!(reference.username.as_deref(), Some("admin"));
+        assert!(placeholder(r#"{{ dplock }}"#).is_err());
+        assert!(placeholder(r#"{{ dplock "a" "b" "c" "d" }}"#).is_err());
+    }
+
+    #[test]
+    fn unescapes_quotes_and_backslashes() {
+        let reference = placeholder(r#"{{ dplock "say \"hi\"" "C:\\path" }}"#).unwrap();
+        assert_eq!(reference.name, r#"say "hi""#);
+        assert_eq!(reference.field.as_deref(), Some(r"C:\path"));
+        assert_eq!(unescape(r"trailing\"), r"trailing\");
+    }
+
+    #[test]
+    fn render_fills_known_references_and_keeps_the_rest() {
+        let mut data = VaultData::default();
+        data.add("prod/db", Entry::new("admin", "s3cret")).unwrap();
+        let template = "user: {{ dplock \"prod/db\" \"username\" }}\npass: {{ dplock \"prod/db\" }}\nother: {{ dplock \"missing\" }}\nplain: {{ value }} {{dplock github}}\n";
+
+        let (rendered, count, unresolved) = render(template, &data).unwrap();
+        assert_eq!(rendered, "user: admin\npass: s3cret\nother: {{ dplock \"missing\" }}\nplain: {{ value }} {{dplock github}}\n");
+        assert_eq!(count, 3);
+        let [problem] = unresolved.as_slice() else { panic!("one unresolved reference expected") };
+        assert_eq!((problem.line, problem.placeholder.as_str()), (3, "{{ dplock \"missing\" }}"));
+    }
+}
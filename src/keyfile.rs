@@ -0,0 +1,94 @@
+use crate::error::{Error, Result};
+use crate::utils::write_secret_file;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Size of keyfiles written by `generate`. Any other non-empty file works as a keyfile too.
+const KEYFILE_LEN: usize = 64;
+
+/// The second factor of a vault: a digest of the keyfile, given to Argon2 as its secret input
+/// next to the master password.
+#[derive(Clone)]
+pub struct Keyfile {
+    digest: Zeroizing<[u8; 32]>,
+}
+
+impl Keyfile {
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = Zeroizing::new(fs::read(path)
+            .map_err(|e| Error::Keyfile(format!("Cannot read keyfile {}: {}", path.display(), e)))?);
+        if contents.is_empty() {
+            return Err(Error::Keyfile(format!("Keyfile {} is empty", path.display())));
+        }
+        let mut digest = Zeroizing::new([0u8; 32]);
+        digest.copy_from_slice(&Sha256::digest(contents.as_slice()));
+        Ok(Self { digest })
+    }
+
+    pub(crate) fn secret(&self) -> &[u8] {
+        self.digest.as_slice()
+    }
+}
+
+/// Writes a new keyfile of random bytes (mode 0600). Never overwrites a file, since that
+/// would lock out the vaults that use it.
+pub fn generate(path: &Path) -> Result<()> {
+    if path.exists() {
+        return Err(Error::Keyfile(format!("{} already exists. Keyfiles are never overwritten", path.display())));
+    }
+    let mut contents = Zeroizing::new([0u8; KEYFILE_LEN]);
+    OsRng.fill_bytes(contents.as_mut_slice());
+    write_secret_file(path, contents.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{self, VaultKey};
+    use tempfile::tempdir;
+
+    #[test]
+    fn generate_writes_a_private_file_once() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("key");
+        generate(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), KEYFILE_LEN);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        let before = fs::read(&path).unwrap();
+        assert!(matches!(generate(&path), Err(Error::Keyfile(_))));
+        assert_eq!(fs::read(&path).unwrap(), before);
+    }
+
+    #[test]
+    fn read_rejects_missing_and_empty_files() {
+        let dir = tempdir().unwrap();
+        assert!(matches!(Keyfile::read(&dir.path().join("missing")), Err(Error::Keyfile(_))));
+        fs::write(dir.path().join("empty"), "").unwrap();
+        assert!(matches!(Keyfile::read(&dir.path().join("empty")), Err(Error::Keyfile(_))));
+    }
+
+    #[test]
+    fn vault_key_requires_the_same_keyfile() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("one"), "first keyfile").unwrap();
+        fs::write(dir.path().join("two"), "second keyfile").unwrap();
+        let (one, two) = (Keyfile::read(&dir.path().join("one")).unwrap(), Keyfile::read(&dir.path().join("two")).unwrap());
+
+        let key = VaultKey::generate("master", Some(&one)).unwrap();
+        let bytes = crypto::encrypt_with_key(b"payload", &key).unwrap();
+        assert!(crypto::requires_keyfile(&bytes));
+        assert!(matches!(VaultKey::for_vault(&bytes, "master", None), Err(Error::Keyfile(_))));
+        // Without wrapped keys a wrong key only shows when decrypting.
+        let wrong = VaultKey::for_vault(&bytes, "master", Some(&two)).unwrap();
+        assert!(matches!(crypto::decrypt_with_key(&bytes, &wrong), Err(Error::WrongPassword)));
+        let opened = VaultKey::for_vault(&bytes, "master", Some(&one)).unwrap();
+        assert_eq!(&*crypto::decrypt_with_key(&bytes, &opened).unwrap(), b"payload");
+    }
+}
@@ -0,0 +1,38 @@
+//! The dplock vault as a library: open a vault file, unlock it, query and change its entries,
+//! and save it back. The `dplock` command-line tool is a front end over this crate.
+//!
+//! ```no_run
+//! use dplock::{Entry, Vault};
+//!
+//! # fn main() -> dplock::Result<()> {
+//! let vault = Vault::open(Vault::default_path());
+//! let key = vault.unlock("master password")?;
+//!
+//! let data = vault.read(&key)?;
+//! for found in data.search("github", None, false) {
+//!     println!("{} (#{}): {}", found.name, found.index + 1, found.entry.username);
+//! }
+//!
+//! vault.update(&key, |data| data.add("work/ci", Entry::new("bot", "s3cret")).map(|_| ()))?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod agent;
+pub mod crypto;
+mod error;
+pub mod folder;
+pub mod fuzzy;
+pub mod generator;
+pub mod keyfile;
+pub mod lock;
+pub mod recovery;
+pub mod template;
+pub mod totp;
+pub mod utils;
+pub mod vault;
+
+pub use error::{Error, Result};
+pub use keyfile::Keyfile;
+pub use recovery::{RecoveryKey, Share};
+pub use vault::{CustomField, Entry, MasterKey, Match, PreviousPassword, Vault, VaultData};
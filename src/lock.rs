@@ -0,0 +1,77 @@
+use crate::error::{Error, Result};
+use fs4::{FileExt, TryLockError};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Advisory lock on `<vault>.lock`, held for as long as the value is alive.
+pub struct VaultLock {
+    _file: File,
+}
+
+impl VaultLock {
+    /// Lock for a read-modify-write cycle; excludes every other reader and writer.
+    /// `on_wait` runs once if another process holds the lock, before waiting for it.
+    pub fn exclusive(vault_path: &Path, on_wait: impl FnOnce()) -> Result<Self> {
+        Self::acquire(vault_path, true, on_wait)
+    }
+
+    /// Lock for reading; other readers may hold it at the same time.
+    pub fn shared(vault_path: &Path, on_wait: impl FnOnce()) -> Result<Self> {
+        Self::acquire(vault_path, false, on_wait)
+    }
+
+    fn lock_path(vault_path: &Path) -> PathBuf {
+        let file_name = vault_path.file_name().unwrap_or_default().to_string_lossy();
+        vault_path.with_file_name(format!("{}.lock", file_name))
+    }
+
+    fn timeout() -> Duration {
+        let secs = env::var("DPLOCK_LOCK_TIMEOUT")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(10); // Default to 10 seconds
+        Duration::from_secs(secs)
+    }
+
+    fn acquire(vault_path: &Path, exclusive: bool, on_wait: impl FnOnce()) -> Result<Self> {
+        let lock_path = Self::lock_path(vault_path);
+        if let Some(parent) = lock_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&lock_path)?;
+
+        let deadline = Instant::now() + Self::timeout();
+        let mut on_wait = Some(on_wait);
+
+        loop {
+            // Called through the trait so newer toolchains don't pick std's inherent `File::try_lock`.
+            let result = if exclusive {
+                FileExt::try_lock(&file)
+            } else {
+                FileExt::try_lock_shared(&file)
+            };
+
+            match result {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) => {
+                    if Instant::now() >= deadline {
+                        return Err(Error::Busy(lock_path));
+                    }
+                    if let Some(on_wait) = on_wait.take() {
+                        on_wait();
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+                Err(TryLockError::Error(e)) => return Err(Error::Other(format!("Failed to lock vault: {e}"))),
+            }
+        }
+    }
+}
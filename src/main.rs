@@ -1,36 +1,128 @@
 mod cli;
-mod crypto;
-mod vault;
-mod utils;
+mod clipboard;
+mod editor;
+mod inject;
+mod picker;
+mod qr;
+mod reference;
+mod run;
+mod session;
+mod tui;
+mod unlock;
 
 use anyhow::Result;
 use cli::build_cli;
-use vault::Vault;
+use dplock::{agent, keyfile, Error};
+use dplock::generator::{self, PassphrasePolicy, PasswordPolicy, Policy};
+use dplock::template::EntryKind;
+use session::{AddOptions, ConflictMode, EditOptions, FieldInput, ListFilter, MatchMode, OutputFormat, ReminderStatus, Session};
 use std::path::PathBuf;
+use unlock::PasswordSource;
 
-fn main() -> Result<()> {
+fn main() {
     let matches = build_cli().get_matches();
+    let emoji = !matches.get_flag("no-emoji") && std::env::var_os("DPLOCK_NO_EMOJI").is_none();
 
+    if let Err(e) = dispatch(&matches) {
+        let message = e.to_string();
+        if emoji {
+            eprintln!("❌ {}", message.trim_start_matches(['❌', '⚠', '\u{fe0f}', ' ']));
+        } else {
+            eprintln!("Error: {}", strip_emoji(&message));
+        }
+        std::process::exit(exit_code(&e));
+    }
+}
+
+/// `message` without emoji, for terminals and logs that can't show them: `(👤 bob)` becomes
+/// `(bob)`.
+fn strip_emoji(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_emoji(c) {
+            while chars.next_if(|&next| is_emoji(next)).is_some() {}
+            chars.next_if_eq(&' ');
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().to_string()
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c,
+        '\u{1f000}'..='\u{1faff}' // pictographs, emoticons, symbols
+        | '\u{2300}'..='\u{23ff}' // ⌛ ⏰ ⏳
+        | '\u{2600}'..='\u{27bf}' // ⚠ ✅ ❌
+        | '\u{2b00}'..='\u{2bff}'
+        | '\u{2139}' // ℹ
+        | '\u{200d}' | '\u{fe0f}') // joiner and emoji presentation selector
+}
+
+/// Exit codes for failures, as documented in the README. 2 is taken by usage errors, and
+/// 20 and 21 by `check-reminders`.
+fn exit_code(error: &anyhow::Error) -> i32 {
+    match error.downcast_ref::<dplock::Error>() {
+        Some(Error::WrongPassword) => 10,
+        Some(Error::VaultNotFound(_)) => 11,
+        Some(Error::CorruptVault(_)) => 12,
+        Some(Error::UnsupportedVersion(_)) => 13,
+        Some(Error::Locked) => 14,
+        Some(Error::Keyring(_)) => 15,
+        Some(Error::Parse(_)) => 16,
+        Some(Error::Keyfile(_)) => 17,
+        Some(Error::Recovery(_)) => 18,
+        Some(Error::Conflict(_)) => 19,
+        Some(Error::Busy(_)) => 22,
+        _ => 1,
+    }
+}
+
+fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     let vault_file = matches.get_one::<String>("vault").map(PathBuf::from);
-    let mut vault = Vault::new(vault_file);
+    let format = OutputFormat::parse(matches.get_one::<String>("format").unwrap())?;
+    let password_source = PasswordSource::from_args(
+        matches.get_one::<u32>("password-fd").copied(),
+        matches.get_one::<String>("password-file").map(String::as_str),
+    );
+    let keyfile = matches.get_one::<String>("keyfile").map(PathBuf::from)
+        .or_else(|| std::env::var_os("DPLOCK_KEYFILE").map(PathBuf::from));
+    let mut session = Session::new(vault_file, keyfile, format, password_source);
 
     if let Some((cmd, sub)) = matches.subcommand() {
-        handle_subcommand(&mut vault, cmd, sub)?;
+        handle_subcommand(&mut session, cmd, sub)?;
     }
 
     Ok(())
 }
 
-fn handle_subcommand(vault: &mut Vault, cmd: &str, sub: &clap::ArgMatches) -> Result<()> {
+fn handle_subcommand(session: &mut Session, cmd: &str, sub: &clap::ArgMatches) -> Result<()> {
     match cmd {
-        "init" => vault.init()?,
-        "add" => handle_add(vault, sub)?,
-        "get" => handle_get(vault, sub)?,
-        "list" => handle_list(vault, sub)?,
-        "remove" => handle_remove(vault, sub)?,
-        "export" => handle_export(vault, sub)?,
-        "import" => handle_import(vault, sub)?,
-        "check-reminders" => vault.check_reminders()?,
+        "init" => session.init()?,
+        "add" => handle_add(session, sub)?,
+        "get" => handle_get(session, sub)?,
+        "otp" => handle_otp(session, sub)?,
+        "qr" => handle_qr(session, sub)?,
+        "edit" => handle_edit(session, sub)?,
+        "history" => handle_history(session, sub)?,
+        "mv" => handle_mv(session, sub)?,
+        "list" => handle_list(session, sub)?,
+        "remove" => handle_remove(session, sub)?,
+        "export" => handle_export(session, sub)?,
+        "import" => handle_import(session, sub)?,
+        "passwd" => handle_passwd(session, sub)?,
+        "backups" => handle_backups(session, sub)?,
+        "generate" => handle_generate(sub)?,
+        "run" => handle_run(session, sub)?,
+        "inject" => handle_inject(session, sub)?,
+        "tui" => tui::run(session)?,
+        "agent" => handle_agent(sub)?,
+        "lock" => handle_lock(session)?,
+        "keyfile" => handle_keyfile(sub)?,
+        "recovery" => handle_recovery(session, sub)?,
+        "check-reminders" => handle_check_reminders(session, sub)?,
+        clipboard::GUARD_COMMAND => clipboard::run_guard(*sub.get_one::<u64>("timeout").unwrap())?,
         _ => {
             println!("❌ Unknown command: {}", cmd);
         }
@@ -38,42 +130,315 @@ fn handle_subcommand(vault: &mut Vault, cmd: &str, sub: &clap::ArgMatches) -> Re
     Ok(())
 }
 
-fn handle_add(vault: &mut Vault, sub: &clap::ArgMatches) -> Result<()> {
+fn handle_add(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
     let name = sub.get_one::<String>("name").unwrap();
     let username = sub.get_one::<String>("username").unwrap();
-    let use_time = sub.get_flag("time");
-    let message = sub.get_one::<String>("message").map(String::as_str);
-    vault.add(name, username, use_time, message)
+    let kind = sub.get_one::<String>("template").map(|t| EntryKind::parse(t)).transpose()?;
+    let options = AddOptions {
+        use_time: sub.get_flag("time"),
+        message: sub.get_one::<String>("message").map(String::as_str),
+        generate: sub.get_one::<String>("generate").map(String::as_str),
+        password_stdin: sub.get_flag("password-stdin"),
+        totp: sub.get_flag("totp"),
+        kind,
+        fields: field_inputs(sub),
+        tags: sub.get_many::<String>("tag").unwrap_or_default().cloned().collect(),
+    };
+    session.add(name, username, options)
+}
+
+/// Collects `--field` and `--secret-field` in the order they were given on the command line.
+fn field_inputs(sub: &clap::ArgMatches) -> Vec<FieldInput> {
+    let mut inputs: Vec<(usize, FieldInput)> = Vec::new();
+    if let (Some(values), Some(indices)) = (sub.get_many::<String>("field"), sub.indices_of("field")) {
+        for (index, value) in indices.zip(values) {
+            let (name, value) = match value.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (value.as_str(), None),
+            };
+            inputs.push((index, FieldInput { name: name.trim().to_string(), secret: false, value }));
+        }
+    }
+    if let (Some(values), Some(indices)) = (sub.get_many::<String>("secret-field"), sub.indices_of("secret-field")) {
+        for (index, name) in indices.zip(values) {
+            inputs.push((index, FieldInput { name: name.trim().to_string(), secret: true, value: None }));
+        }
+    }
+    inputs.sort_by_key(|(index, _)| *index);
+    inputs.into_iter().map(|(_, input)| input).collect()
+}
+
+fn handle_get(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let name = sub.get_one::<String>("name").unwrap();
+    let username = sub.get_one::<String>("username").map(String::as_str);
+    let show = sub.get_flag("show");
+    let mode = if sub.get_flag("exact") {
+        MatchMode::Exact
+    } else if sub.get_flag("regex") {
+        MatchMode::Regex
+    } else {
+        MatchMode::Fuzzy
+    };
+    let field = sub.get_one::<String>("field").map(String::as_str);
+    let no_clear = sub.get_flag("no-clear");
+    session.get(name, username, mode, field, show, no_clear)
 }
 
-fn handle_get(vault: &mut Vault, sub: &clap::ArgMatches) -> Result<()> {
+fn handle_otp(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
     let name = sub.get_one::<String>("name").unwrap();
     let username = sub.get_one::<String>("username").map(String::as_str);
     let show = sub.get_flag("show");
-    vault.get(name, username, show)
+    let set = sub.get_flag("set");
+    let no_clear = sub.get_flag("no-clear");
+    session.otp(name, username, show, set, no_clear)
 }
 
-fn handle_list(vault: &mut Vault, sub: &clap::ArgMatches) -> Result<()> {
-    let filter = sub.get_one::<String>("filter").map(String::as_str);
+fn handle_qr(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let name = sub.get_one::<String>("name").unwrap();
+    let username = sub.get_one::<String>("username").map(String::as_str);
+    let wifi = sub.get_one::<String>("wifi").map(String::as_str);
+    let svg = sub.get_one::<String>("svg").map(String::as_str);
+    session.qr(name, username, wifi, svg)
+}
+
+fn handle_edit(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let name = sub.get_one::<String>("name").unwrap();
+    let options = EditOptions {
+        index: sub.get_one::<usize>("index").copied(),
+        username: sub.get_one::<String>("username").map(String::as_str),
+        password: sub.get_flag("password"),
+        generate: sub.get_one::<String>("generate").map(String::as_str),
+        message: sub.get_one::<String>("message").map(String::as_str),
+        expires: sub.get_one::<String>("expires").map(String::as_str),
+        remind: sub.get_one::<String>("remind").map(String::as_str),
+        kind: sub.get_one::<String>("template").map(|t| EntryKind::parse(t)).transpose()?,
+        fields: field_inputs(sub),
+        remove_fields: sub.get_many::<String>("remove-field").unwrap_or_default().cloned().collect(),
+        tags: sub.get_many::<String>("tag").unwrap_or_default().cloned().collect(),
+        untags: sub.get_many::<String>("untag").unwrap_or_default().cloned().collect(),
+        editor: sub.get_flag("editor"),
+    };
+    session.edit(name, options)
+}
+
+fn handle_history(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let name = sub.get_one::<String>("name").unwrap();
+    let index = sub.get_one::<usize>("index").copied();
+    let restore = sub.get_one::<usize>("restore").copied();
+    let show = sub.get_flag("show");
+    session.history(name, index, restore, show)
+}
+
+fn handle_mv(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let source = sub.get_one::<String>("source").unwrap();
+    let target = sub.get_one::<String>("target").unwrap();
+    let index = sub.get_one::<usize>("index").copied();
+    let on_conflict = ConflictMode::parse(sub.get_one::<String>("on-conflict").unwrap())?;
+    session.move_entries(source, target, index, on_conflict)
+}
+
+fn handle_list(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let filter = ListFilter {
+        text: sub.get_one::<String>("filter").map(String::as_str),
+        tags: sub.get_many::<String>("tag").unwrap_or_default().cloned().collect(),
+        folder: sub.get_one::<String>("folder").map(String::as_str),
+    };
     let sort = sub.get_one::<String>("sort").map(String::as_str);
-    vault.list(filter, sort)
+    let tree = sub.get_flag("tree");
+    session.list(&filter, sort, tree)
 }
 
-fn handle_remove(vault: &mut Vault, sub: &clap::ArgMatches) -> Result<()> {
+fn handle_remove(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
     let name = sub.get_one::<String>("name").unwrap();
     let index = sub.get_one::<String>("index")
         .map(|i| i.parse::<usize>().unwrap_or(0));
-    vault.remove(name, index)
+    session.remove(name, index)
 }
 
-fn handle_export(vault: &mut Vault, sub: &clap::ArgMatches) -> Result<()> {
+fn handle_export(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
     let path = sub.get_one::<String>("path").unwrap();
     let plain = sub.get_flag("plain");
-    vault.export(path, plain)
+    session.export(path, plain)
 }
 
-fn handle_import(vault: &mut Vault, sub: &clap::ArgMatches) -> Result<()> {
+fn handle_import(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
     let path = sub.get_one::<String>("path").unwrap();
     let plain = sub.get_flag("plain");
-    vault.import(path, plain)
-}
\ No newline at end of file
+    session.import(path, plain)
+}
+
+fn handle_passwd(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let export = sub.get_one::<String>("export").map(String::as_str);
+    session.passwd(export)
+}
+
+fn handle_backups(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    match sub.subcommand() {
+        Some(("list", _)) => session.list_backups(),
+        Some(("restore", restore)) => {
+            let n = restore.get_one::<String>("n").unwrap();
+            let n = n.parse::<usize>().map_err(|_| anyhow::anyhow!("❌ Invalid backup number: {}", n))?;
+            session.restore_backup(n)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn handle_agent(sub: &clap::ArgMatches) -> Result<()> {
+    match sub.subcommand() {
+        Some(("start", start)) if start.get_flag("foreground") => {
+            let server = agent::listen()?;
+            eprintln!("🛡️  Agent listening on {}", server.path().display());
+            server.run()?;
+            eprintln!("👋 Agent stopped.");
+            Ok(())
+        }
+        Some(("start", _)) => {
+            if session::start_agent()? {
+                println!("🛡️  Agent started: {}", agent::socket_path().display());
+            } else {
+                println!("ℹ️  Agent already running: {}", agent::socket_path().display());
+            }
+            Ok(())
+        }
+        Some(("stop", _)) => {
+            if agent::stop() {
+                println!("👋 Agent stopped. All keys wiped.");
+            } else {
+                println!("💤 No agent is running.");
+            }
+            Ok(())
+        }
+        Some(("status", _)) => {
+            let Some(vaults) = agent::status() else {
+                println!("💤 No agent is running.");
+                return Ok(());
+            };
+            println!("🛡️  Agent running (socket: {})", agent::socket_path().display());
+            if vaults.is_empty() {
+                println!("   🔒 No unlocked vaults.");
+            }
+            for status in vaults {
+                println!("   🔓 {} — idle {}s, locks in {}s", status.vault.display(), status.idle_secs, status.locks_in_secs);
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn handle_keyfile(sub: &clap::ArgMatches) -> Result<()> {
+    if let Some(("generate", generate)) = sub.subcommand() {
+        let path = PathBuf::from(generate.get_one::<String>("path").unwrap());
+        keyfile::generate(&path)?;
+        println!("🔑 Keyfile written to: {}", path.display());
+        println!("   Keep a copy apart from the vault. Without it, a vault that requires it cannot be opened.");
+    }
+    Ok(())
+}
+
+fn handle_recovery(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    match sub.subcommand() {
+        Some(("create", create)) => session.recovery_create(
+            *create.get_one::<u8>("shares").unwrap(),
+            *create.get_one::<u8>("threshold").unwrap(),
+            create.get_flag("qr"),
+            create.get_one::<String>("svg").map(String::as_str),
+        ),
+        Some(("restore", restore)) => session.recovery_restore(
+            restore.get_one::<String>("new-password-file").map(String::as_str),
+            restore.get_flag("drop-keyfile"),
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn handle_lock(session: &mut Session) -> Result<()> {
+    let _ = session.clear_master_from_keyring(); // plaintext cache of older versions
+    if agent::lock() {
+        println!("🔒 All vaults locked. The agent holds no keys.");
+    } else {
+        println!("🔒 Nothing to lock: no agent is running.");
+    }
+    Ok(())
+}
+
+fn handle_run(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let mappings = sub.get_many::<String>("env").unwrap_or_default()
+        .map(|mapping| run::parse_mapping(mapping))
+        .collect::<Result<Vec<_>>>()?;
+    let config = sub.get_one::<String>("config").map(String::as_str);
+    let command: Vec<String> = sub.get_many::<String>("command").unwrap_or_default().cloned().collect();
+    run::run(session, mappings, config, &command)
+}
+
+fn handle_inject(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let input = sub.get_one::<String>("input").unwrap();
+    let output = sub.get_one::<String>("output").map(String::as_str);
+    let strict = sub.get_flag("strict");
+    let check = sub.get_flag("check");
+    inject::inject(session, input, output, strict, check)
+}
+
+fn handle_check_reminders(session: &mut Session, sub: &clap::ArgMatches) -> Result<()> {
+    let within = sub.get_one::<String>("within").map(String::as_str);
+    let json = sub.get_flag("json") || session.format() == OutputFormat::Json;
+    let status = session.check_reminders(within, json)?;
+    if status != ReminderStatus::NothingDue {
+        std::process::exit(status.exit_code());
+    }
+    Ok(())
+}
+
+fn handle_generate(sub: &clap::ArgMatches) -> Result<()> {
+    let parse_number = |id: &str| -> Result<Option<usize>> {
+        sub.get_one::<String>(id)
+            .map(|v| v.parse::<usize>().map_err(|_| anyhow::anyhow!("❌ Invalid --{}: {}", id, v)))
+            .transpose()
+    };
+
+    let words = parse_number("words")?;
+    let policy = if sub.get_flag("passphrase") || words.is_some() {
+        let mut policy = PassphrasePolicy::default();
+        if let Some(words) = words {
+            policy.words = words;
+        }
+        if let Some(separator) = sub.get_one::<String>("separator") {
+            policy.separator = separator.clone();
+        }
+        Policy::Passphrase(policy)
+    } else {
+        let mut policy = PasswordPolicy::default();
+        if let Some(length) = parse_number("length")? {
+            policy.length = length;
+        }
+        policy.lowercase = !sub.get_flag("no-lower");
+        policy.uppercase = !sub.get_flag("no-upper");
+        policy.digits = !sub.get_flag("no-digits");
+        policy.symbols = !sub.get_flag("no-symbols");
+        policy.exclude_ambiguous = sub.get_flag("exclude-ambiguous");
+        policy.require_each = !sub.get_flag("no-require");
+        Policy::Password(policy)
+    };
+
+    let count = parse_number("count")?.unwrap_or(1);
+    for _ in 0..count {
+        let generated = generator::generate(&policy)?;
+        println!("{}", generated.value);
+        eprintln!("🎲 Entropy: ~{:.0} bits ({})", generated.entropy_bits, generator::strength_label(generated.entropy_bits));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_emoji_removes_markers_inside_messages() {
+        assert_eq!(strip_emoji("❌ No field 'x' on y (👤 z)."), "No field 'x' on y (z).");
+        assert_eq!(strip_emoji("⚠️  Several entries match"), "Several entries match");
+        assert_eq!(strip_emoji("🔑 a 🛡️ b"), "a b");
+        assert_eq!(strip_emoji("plain: ü, é → ok"), "plain: ü, é → ok");
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use crossterm::cursor::{MoveToColumn, MoveUp};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType};
+use crossterm::{execute, queue};
+use std::io::{self, IsTerminal, Write};
+
+use dplock::utils::get_terminal_width;
+
+const VISIBLE_ROWS: usize = 10;
+
+/// Whether a picker can be shown: both the keyboard and the terminal are interactive.
+pub fn is_available() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Restores the terminal even if drawing fails half-way.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Lets the user choose one of `items` with the arrow keys (or j/k) and Enter. Drawn inline on
+/// stderr so stdout stays clean. Returns `None` when cancelled with Esc, q or Ctrl-C.
+pub fn pick(prompt: &str, items: &[String]) -> Result<Option<usize>> {
+    if items.is_empty() {
+        return Ok(None);
+    }
+    let mut out = io::stderr();
+    let rows = items.len().min(VISIBLE_ROWS);
+    let width = get_terminal_width().saturating_sub(4).max(10);
+    let mut selected = 0;
+    let mut offset = 0;
+
+    let _raw = RawMode::enable()?;
+    let choice = loop {
+        queue!(out, MoveToColumn(0), Clear(ClearType::FromCursorDown), Print(format!("{} (↑/↓, Enter, Esc)\r\n", prompt)))?;
+        for (i, item) in items.iter().enumerate().skip(offset).take(rows) {
+            // Lines must not wrap, or redrawing would move up by the wrong amount.
+            let text: String = item.chars().take(width).collect();
+            if i == selected {
+                queue!(out, SetAttribute(Attribute::Reverse), Print(format!("> {}", text)), SetAttribute(Attribute::Reset), Print("\r\n"))?;
+            } else {
+                queue!(out, Print(format!("  {}\r\n", text)))?;
+            }
+        }
+        out.flush()?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => {
+                queue!(out, MoveUp(rows as u16 + 1))?;
+                continue;
+            }
+        };
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => selected = (selected + 1).min(items.len() - 1),
+            KeyCode::PageUp => selected = selected.saturating_sub(rows),
+            KeyCode::PageDown => selected = (selected + rows).min(items.len() - 1),
+            KeyCode::Home => selected = 0,
+            KeyCode::End => selected = items.len() - 1,
+            KeyCode::Enter => break Some(selected),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break None,
+            KeyCode::Esc | KeyCode::Char('q') => break None,
+            _ => {}
+        }
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + rows {
+            offset = selected + 1 - rows;
+        }
+        queue!(out, MoveUp(rows as u16 + 1))?;
+    };
+
+    execute!(out, MoveUp(rows as u16 + 1), MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+    Ok(choice)
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use qrcodegen::{QrCode, QrCodeEcc};
+
+const BORDER: i32 = 2;
+
+fn encode(payload: &str) -> Result<QrCode> {
+    QrCode::encode_text(payload, QrCodeEcc::Medium)
+        .map_err(|_| anyhow!("❌ Data is too long to fit in a QR code."))
+}
+
+/// Renders two module rows per line with Unicode half blocks. Light modules are drawn,
+/// so the code scans on the usual dark terminal background.
+pub fn to_terminal(payload: &str) -> Result<String> {
+    let qr = encode(payload)?;
+    let size = qr.size();
+    let light = |x: i32, y: i32| !qr.get_module(x, y); // out of range counts as light
+
+    let mut out = String::new();
+    for y in (-BORDER..size + BORDER).step_by(2) {
+        for x in -BORDER..size + BORDER {
+            out.push(match (light(x, y), light(x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+pub fn to_svg(payload: &str) -> Result<String> {
+    let qr = encode(payload)?;
+    let size = qr.size();
+    let dimension = size + BORDER * 2;
+
+    let mut path = String::new();
+    for y in 0..size {
+        for x in 0..size {
+            if qr.get_module(x, y) {
+                if !path.is_empty() {
+                    path.push(' ');
+                }
+                path.push_str(&format!("M{},{}h1v1h-1z", x + BORDER, y + BORDER));
+            }
+        }
+    }
+
+    Ok(format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" viewBox=\"0 0 {0} {0}\" stroke=\"none\">\n\
+         \t<rect width=\"100%\" height=\"100%\" fill=\"#FFFFFF\"/>\n\
+         \t<path d=\"{1}\" fill=\"#000000\"/>\n\
+         </svg>\n",
+        dimension, path
+    ))
+}
+
+/// Builds a `WIFI:` payload understood by phone cameras, using the entry's username as the SSID.
+pub fn wifi_payload(ssid: &str, password: &str, security: &str) -> Result<String> {
+    let security = match security.to_uppercase().as_str() {
+        "WPA" | "WPA2" | "WPA3" => "WPA",
+        "WEP" => "WEP",
+        "NOPASS" | "NONE" => "nopass",
+        _ => return Err(anyhow!("❌ Unknown Wi-Fi security '{}'. Use WPA, WEP or nopass.", security)),
+    };
+
+    let mut payload = format!("WIFI:T:{};S:{};", security, escape_wifi(ssid));
+    if security != "nopass" {
+        payload.push_str(&format!("P:{};", escape_wifi(password)));
+    }
+    payload.push(';');
+    Ok(payload)
+}
+
+fn escape_wifi(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ';' | ',' | '"' | ':') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
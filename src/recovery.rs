@@ -0,0 +1,261 @@
+use crate::error::{Error, Result};
+use crate::generator::WORDLIST;
+use rand::{rngs::OsRng, Rng, RngCore};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+// id | threshold | index | value, then one checksum byte
+const SHARE_LEN: usize = 2 + 1 + 1 + 32;
+const BITS_PER_WORD: usize = 11;
+/// Words of a share written out with `to_words`.
+pub const SHARE_WORDS: usize = ((SHARE_LEN + 1) * 8).div_ceil(BITS_PER_WORD);
+
+/// A random key that can unwrap the vault key next to the master password. It is never stored
+/// as a whole; `split` turns it into shares, any `threshold` of which restore it.
+pub struct RecoveryKey(Zeroizing<[u8; 32]>);
+
+/// One part of a recovery key (Shamir's secret sharing over GF(256)). Shares of the same kit
+/// carry the same random id, so shares of different kits aren't mixed up.
+#[derive(Clone)]
+pub struct Share {
+    id: u16,
+    threshold: u8,
+    index: u8,
+    value: Zeroizing<[u8; 32]>,
+}
+
+impl RecoveryKey {
+    pub fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(key.as_mut_slice());
+        Self(key)
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// `shares` shares, any `threshold` of which restore the key.
+    pub fn split(&self, shares: u8, threshold: u8) -> Result<Vec<Share>> {
+        if threshold == 0 || threshold > shares {
+            return Err(Error::parse(format!("The threshold must be between 1 and the number of shares ({})", shares)));
+        }
+        let id: u16 = OsRng.gen();
+        // One polynomial per byte of the key, with that byte as its constant term.
+        let mut coefficients = Zeroizing::new(vec![[0u8; 32]; threshold as usize]);
+        coefficients[0] = *self.0;
+        for row in coefficients.iter_mut().skip(1) {
+            OsRng.fill_bytes(row);
+        }
+
+        Ok((1..=shares).map(|x| {
+            let mut value = Zeroizing::new([0u8; 32]);
+            for (i, byte) in value.iter_mut().enumerate() {
+                // Horner's rule, highest coefficient first.
+                *byte = coefficients.iter().rev().fold(0, |acc, row| gf_mul(acc, x) ^ row[i]);
+            }
+            Share { id, threshold, index: x, value }
+        }).collect())
+    }
+
+    /// The key from at least `threshold` shares of one kit.
+    pub fn combine(shares: &[Share]) -> Result<Self> {
+        let first = shares.first().ok_or_else(|| Error::Recovery("No shares given".to_string()))?;
+        if shares.iter().any(|share| share.id != first.id || share.threshold != first.threshold) {
+            return Err(Error::Recovery("The shares are from different recovery kits".to_string()));
+        }
+        for (i, share) in shares.iter().enumerate() {
+            if shares[..i].iter().any(|other| other.index == share.index) {
+                return Err(Error::Recovery(format!("Share {} is given twice", share.index)));
+            }
+        }
+        if shares.len() < first.threshold as usize {
+            return Err(Error::Recovery(format!("{} shares are needed; only {} given", first.threshold, shares.len())));
+        }
+
+        // Lagrange interpolation at x = 0; subtraction is XOR in GF(256).
+        let shares = &shares[..first.threshold as usize];
+        let mut key = Zeroizing::new([0u8; 32]);
+        for share in shares {
+            let basis = shares.iter()
+                .filter(|other| other.index != share.index)
+                .fold(1, |acc, other| gf_mul(acc, gf_mul(other.index, gf_inv(other.index ^ share.index))));
+            for (byte, value) in key.iter_mut().zip(share.value.iter()) {
+                *byte ^= gf_mul(basis, *value);
+            }
+        }
+        Ok(Self(key))
+    }
+}
+
+impl Share {
+    /// Position of the share in its kit, from 1.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// How many shares of the kit restore the key.
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// The share as `SHARE_WORDS` words of the passphrase word list, with a checksum.
+    pub fn to_words(&self) -> String {
+        let bytes = self.to_bytes();
+        let wordlist = wordlist();
+        let mut words = Vec::new();
+        let (mut acc, mut bits) = (0u32, 0);
+        for byte in bytes.iter() {
+            acc = (acc << 8) | *byte as u32;
+            bits += 8;
+            while bits >= BITS_PER_WORD {
+                bits -= BITS_PER_WORD;
+                words.push(wordlist[(acc >> bits) as usize & 0x7ff]);
+                acc &= (1 << bits) - 1;
+            }
+        }
+        if bits > 0 {
+            words.push(wordlist[(acc << (BITS_PER_WORD - bits)) as usize & 0x7ff]);
+        }
+        words.join(" ")
+    }
+
+    pub fn from_words(text: &str) -> Result<Self> {
+        let wordlist = wordlist();
+        let mut bytes = Zeroizing::new(Vec::with_capacity(SHARE_LEN + 1));
+        let (mut acc, mut bits) = (0u32, 0);
+        for word in text.split_whitespace() {
+            let word = word.to_lowercase();
+            let index = wordlist.iter().position(|w| *w == word)
+                .ok_or_else(|| Error::Recovery(format!("'{}' is not a word of a recovery share", word)))?;
+            acc = (acc << BITS_PER_WORD) | index as u32;
+            bits += BITS_PER_WORD;
+            while bits >= 8 {
+                bits -= 8;
+                bytes.push((acc >> bits) as u8);
+                acc &= (1 << bits) - 1;
+            }
+        }
+        if bytes.len() != SHARE_LEN + 1 || acc != 0 {
+            return Err(Error::Recovery(format!("A recovery share has {} words", SHARE_WORDS)));
+        }
+        if Sha256::digest(&bytes[..SHARE_LEN])[0] != bytes[SHARE_LEN] {
+            return Err(Error::Recovery("The share has a typo: its checksum doesn't match".to_string()));
+        }
+        let mut value = Zeroizing::new([0u8; 32]);
+        value.copy_from_slice(&bytes[4..SHARE_LEN]);
+        let share = Share { id: u16::from_be_bytes([bytes[0], bytes[1]]), threshold: bytes[2], index: bytes[3], value };
+        if share.index == 0 || share.threshold == 0 {
+            return Err(Error::Recovery("Invalid recovery share".to_string()));
+        }
+        Ok(share)
+    }
+
+    fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(SHARE_LEN + 1));
+        bytes.extend(self.id.to_be_bytes());
+        bytes.push(self.threshold);
+        bytes.push(self.index);
+        bytes.extend(self.value.iter());
+        let checksum = Sha256::digest(&bytes[..])[0];
+        bytes.push(checksum);
+        bytes
+    }
+}
+
+fn wordlist() -> Vec<&'static str> {
+    WORDLIST.lines().filter(|w| !w.is_empty()).collect()
+}
+
+/// Multiplication in GF(256) with the AES polynomial.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// `a^254`, the inverse of a non-zero `a`.
+fn gf_inv(a: u8) -> u8 {
+    let (mut result, mut base, mut exponent) = (1, a, 254);
+    while exponent > 0 {
+        if exponent & 1 != 0 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_threshold_shares_restore_the_key() {
+        let key = RecoveryKey::generate();
+        let shares = key.split(5, 3).unwrap();
+        for picked in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let subset: Vec<Share> = picked.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(RecoveryKey::combine(&subset).unwrap().as_bytes(), key.as_bytes());
+        }
+        // Extra shares beyond the threshold don't change the result.
+        assert_eq!(RecoveryKey::combine(&shares).unwrap().as_bytes(), key.as_bytes());
+    }
+
+    #[test]
+    fn too_few_mixed_or_repeated_shares_are_rejected() {
+        let shares = RecoveryKey::generate().split(3, 2).unwrap();
+        let mut other = RecoveryKey::generate().split(3, 2).unwrap().remove(1);
+        other.id = shares[0].id ^ 1;
+        assert!(matches!(RecoveryKey::combine(&shares[..1]), Err(Error::Recovery(_))));
+        assert!(matches!(RecoveryKey::combine(&[shares[0].clone(), other]), Err(Error::Recovery(_))));
+        assert!(matches!(RecoveryKey::combine(&[shares[0].clone(), shares[0].clone()]), Err(Error::Recovery(_))));
+        assert!(matches!(RecoveryKey::generate().split(2, 3), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn words_round_trip() {
+        let key = RecoveryKey::generate();
+        let shares = key.split(2, 2).unwrap();
+        let words: Vec<String> = shares.iter().map(Share::to_words).collect();
+        assert_eq!(words[0].split_whitespace().count(), SHARE_WORDS);
+
+        let parsed: Vec<Share> = words.iter().map(|w| Share::from_words(&w.to_uppercase()).unwrap()).collect();
+        assert_eq!((parsed[1].index(), parsed[1].threshold()), (2, 2));
+        assert_eq!(RecoveryKey::combine(&parsed).unwrap().as_bytes(), key.as_bytes());
+    }
+
+    #[test]
+    fn tampered_word_is_rejected() {
+        // Fixed, so the 8-bit checksum can't match the tampered share by chance.
+        let share = Share { id: 0x1234, threshold: 1, index: 1, value: Zeroizing::new([0x42; 32]) };
+        let text = share.to_words();
+        let mut words: Vec<&str> = text.split(' ').collect();
+        let list = wordlist();
+        // Swap one word for its neighbour in the list; the checksum catches it.
+        let position = list.iter().position(|w| *w == words[5]).unwrap();
+        words[5] = list[(position + 1) % list.len()];
+        assert!(matches!(Share::from_words(&words.join(" ")), Err(Error::Recovery(_))));
+
+        assert!(matches!(Share::from_words(&words[1..].join(" ")), Err(Error::Recovery(_))));
+        assert!(matches!(Share::from_words("notaword"), Err(Error::Recovery(_))));
+    }
+
+    #[test]
+    fn gf_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use dplock::VaultData;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use std::fmt;
+
+/// Points at one value in the vault: an entry name, optionally narrowed by username,
+/// and what to take from the entry (default: its password).
+#[derive(Clone)]
+pub struct SecretRef {
+    pub name: String,
+    pub field: Option<String>,
+    pub username: Option<String>,
+}
+
+impl SecretRef {
+    /// Parses `name[:field]`, e.g. `github` or `prod/db:password`. The field is taken after
+    /// the last colon.
+    pub fn parse(input: &str) -> Result<Self> {
+        let (name, field) = match input.rsplit_once(':') {
+            Some((name, field)) => (name, Some(field.trim().to_string())),
+            None => (input, None),
+        };
+        let invalid = || anyhow!("❌ Invalid secret reference '{}'. Use e.g: github or prod/db:password", input);
+        if name.trim().is_empty() || field.as_deref() == Some("") {
+            return Err(invalid());
+        }
+        Ok(SecretRef { name: name.to_string(), field, username: None })
+    }
+
+    /// Looks the value up by exact name, like `get --exact` (see `VaultData::resolve_name`).
+    /// `password`, `username` and `otp` (the current code) are built in; any other field is one
+    /// of the entry's custom fields.
+    pub fn resolve(&self, data: &VaultData) -> Result<String> {
+        let name = data.resolve_name(&self.name);
+        let entry = &data.get(&name)[data.find(&name, self.username.as_deref())?];
+
+        match self.field.as_deref().unwrap_or("password") {
+            "password" => Ok(entry.password.clone()),
+            "username" => Ok(entry.username.clone()),
+            "otp" => match &entry.totp {
+                Some(totp) => Ok(totp.code_at(Utc::now().timestamp() as u64)?.0),
+                None => Err(anyhow!("❌ No TOTP secret stored under '{}'.", self.name)),
+            },
+            field => entry.field(field)
+                .map(|f| f.value.clone())
+                .ok_or_else(|| anyhow!("❌ No field '{}' on {} (👤 {}).", field, self.name, entry.username)),
+        }
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(field) = &self.field {
+            write!(f, ":{}", field)?;
+        }
+        if let Some(username) = &self.username {
+            write!(f, " (👤 {})", username)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dplock::{CustomField, Entry};
+
+    fn vault_data() -> VaultData {
+        let mut entry = Entry::new("admin", "s3cret");
+        entry.fields.push(CustomField { name: "url".to_string(), value: "https://db".to_string(), secret: false });
+        let mut data = VaultData::default();
+        data.add("prod/db", entry).unwrap();
+        data.entries.insert("aws//key".to_string(), vec![Entry::new("legacy", "old")]);
+        data
+    }
+
+    #[test]
+    fn parse_splits_the_field_at_the_last_colon() {
+        let reference = SecretRef::parse("github").unwrap();
+        assert_eq!((reference.name.as_str(), reference.field), ("github", None));
+        let reference = SecretRef::parse("prod/db: password ").unwrap();
+        assert_eq!((reference.name.as_str(), reference.field.as_deref()), ("prod/db", Some("password")));
+        let reference = SecretRef::parse("host:8080:url").unwrap();
+        assert_eq!((reference.name.as_str(), reference.field.as_deref()), ("host:8080", Some("url")));
+    }
+
+    #[test]
+    fn parse_rejects_empty_names_and_fields() {
+        for input in ["", "  ", ":password", "github:"] {
+            assert!(SecretRef::parse(input).is_err(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn resolve_reads_built_in_and_custom_fields() {
+        let data = vault_data();
+        let value = |input: &str| SecretRef::parse(input).unwrap().resolve(&data);
+        assert_eq!(value("prod/db").unwrap(), "s3cret");
+        assert_eq!(value("prod/db:username").unwrap(), "admin");
+        assert_eq!(value("prod/db:url").unwrap(), "https://db");
+        assert!(value("prod/db:token").is_err());
+        assert!(value("prod/db:otp").is_err());
+        assert!(value("prod").is_err()); // no fuzzy matching
+    }
+
+    #[test]
+    fn resolve_tries_the_exact_name_before_normalizing() {
+        let data = vault_data();
+        let value = |input: &str| SecretRef::parse(input).unwrap().resolve(&data);
+        assert_eq!(value("aws//key").unwrap(), "old");
+        assert_eq!(value("/prod/db/").unwrap(), "s3cret");
+    }
+}
@@ -0,0 +1,158 @@
+use crate::reference::SecretRef;
+use crate::session::Session;
+use anyhow::{anyhow, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const CONFIG_FILE: &str = ".dplock.toml";
+
+/// Parses `VAR=name[:field]` as given to `run --env`.
+pub fn parse_mapping(input: &str) -> Result<(String, SecretRef)> {
+    let (var, reference) = input.split_once('=')
+        .ok_or_else(|| anyhow!("❌ Invalid --env '{}'. Use e.g: DB_PASS=prod/db:password", input))?;
+    Ok((check_var(var)?, SecretRef::parse(reference)?))
+}
+
+fn check_var(var: &str) -> Result<String> {
+    let var = var.trim();
+    if var.is_empty() || var.contains(['=', '\0']) {
+        return Err(anyhow!("❌ Invalid environment variable name '{}'.", var));
+    }
+    Ok(var.to_string())
+}
+
+/// The nearest `.dplock.toml` in the current directory or one of its parents.
+fn find_config() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors().map(|dir| dir.join(CONFIG_FILE)).find(|path| path.is_file())
+}
+
+/// Reads the `[env]` table of a mapping file. Each value is either `"name[:field]"` or a table
+/// `{ name = "...", field = "...", username = "..." }`.
+fn load_config(path: &Path) -> Result<Vec<(String, SecretRef)>> {
+    let text = fs::read_to_string(path).map_err(|e| anyhow!("❌ Failed to read '{}': {e}", path.display()))?;
+    let doc: toml_edit::Document = text.parse().map_err(|e| anyhow!("❌ Invalid TOML in '{}': {e}", path.display()))?;
+    let Some(env) = doc.get("env") else {
+        return Ok(Vec::new());
+    };
+    let env = env.as_table_like().ok_or_else(|| anyhow!("❌ 'env' in '{}' must be a table.", path.display()))?;
+
+    let mut mappings = Vec::new();
+    for (var, item) in env.iter() {
+        let invalid = || anyhow!("❌ Invalid mapping for '{}' in '{}'. Use e.g: {} = \"prod/db:password\"", var, path.display(), var);
+        let reference = if let Some(value) = item.as_str() {
+            SecretRef::parse(value)?
+        } else if let Some(table) = item.as_table_like() {
+            let get = |key: &str| table.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            SecretRef {
+                name: get("name").ok_or_else(invalid)?,
+                field: get("field"),
+                username: get("username"),
+            }
+        } else {
+            return Err(invalid());
+        };
+        mappings.push((check_var(var)?, reference));
+    }
+    Ok(mappings)
+}
+
+/// Runs `command` with the referenced secrets added to its environment. The mappings come from
+/// `config`, or the nearest `.dplock.toml`, with `--env` taking precedence.
+pub fn run(session: &Session, mappings: Vec<(String, SecretRef)>, config: Option<&str>, command: &[String]) -> Result<()> {
+    let config = config.map(PathBuf::from).or_else(find_config);
+    let mut env = match &config {
+        Some(path) => load_config(path)?,
+        None => Vec::new(),
+    };
+    for (var, reference) in mappings {
+        env.retain(|(existing, _)| existing != &var);
+        env.push((var, reference));
+    }
+    if env.is_empty() {
+        return Err(anyhow!("❌ Nothing to inject. Use --env VAR=name[:field] or add an [env] table to {}.", CONFIG_FILE));
+    }
+    let (program, args) = command.split_first().ok_or_else(|| anyhow!("❌ No command given. Use e.g: dplock run --env TOKEN=github -- ./deploy.sh"))?;
+
+    let master = session.unlock()?;
+    let data = session.vault().read(&master)?;
+    let values = env.iter()
+        .map(|(var, reference)| {
+            reference.resolve(&data)
+                .map(|value| (var.clone(), value))
+                .map_err(|e| anyhow!("{} (for {}={})", e, var, reference))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    drop(data);
+
+    let mut child = Command::new(program);
+    child.args(args).envs(values);
+    exec(child, program)
+}
+
+/// Replaces dplock with the command, so it receives signals directly and its exit status is
+/// the one the shell sees.
+#[cfg(unix)]
+fn exec(mut command: Command, program: &str) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+    let error = command.exec();
+    Err(anyhow!("❌ Failed to run '{}': {}", program, error))
+}
+
+#[cfg(not(unix))]
+fn exec(mut command: Command, program: &str) -> Result<()> {
+    let status = command.status().map_err(|e| anyhow!("❌ Failed to run '{}': {e}", program))?;
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config(text: &str) -> Result<Vec<(String, SecretRef)>> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, text).unwrap();
+        load_config(&path)
+    }
+
+    #[test]
+    fn parse_mapping_splits_at_the_first_equals_sign() {
+        let (var, reference) = parse_mapping("DB_PASS=prod/db:password").unwrap();
+        assert_eq!(var, "DB_PASS");
+        assert_eq!((reference.name.as_str(), reference.field.as_deref()), ("prod/db", Some("password")));
+        let (var, reference) = parse_mapping(" TOKEN =a=b").unwrap();
+        assert_eq!((var.as_str(), reference.name.as_str()), ("TOKEN", "a=b"));
+    }
+
+    #[test]
+    fn parse_mapping_rejects_malformed_input() {
+        for input in ["DB_PASS", "=github", "DB_PASS=", "DB_PASS=github:"] {
+            assert!(parse_mapping(input).is_err(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn load_config_reads_strings_and_tables() {
+        let mappings = config(r#"
+            [env]
+            DB_PASS = "prod/db:password"
+            ADMIN = { name = "prod/db", field = "username", username = "admin" }
+        "#).unwrap();
+        let [(db_var, db), (admin_var, admin)] = mappings.as_slice() else { panic!("two mappings expected") };
+        assert_eq!((db_var.as_str(), db.name.as_str(), db.field.as_deref()), ("DB_PASS", "prod/db", Some("password")));
+        assert_eq!(admin_var, "ADMIN");
+        assert_eq!((admin.field.as_deref(), admin.username.as_deref()), (Some("username"), Some("admin")));
+        assert!(config("[other]\nKEY = 1\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn load_config_rejects_malformed_files() {
+        for text in ["[env", "env = 1", "[env]\nTOKEN = 1", "[env]\nTOKEN = { field = \"url\" }", "[env]\nTOKEN = \":url\""] {
+            assert!(config(text).is_err(), "{text:?}");
+        }
+    }
+}
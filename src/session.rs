@@ -0,0 +1,1855 @@
+use crate::clipboard;
+use crate::editor;
+use crate::picker;
+use crate::qr;
+use crate::unlock::{self, PasswordSource, PASSWORD_ENV};
+use dplock::agent;
+use dplock::crypto;
+use dplock::folder;
+use dplock::generator::{self, Policy};
+use dplock::template::{EntryKind, FieldSpec};
+use dplock::totp::Totp;
+use dplock::vault::normalize_tag;
+use dplock::recovery::SHARE_WORDS;
+use dplock::{CustomField, Entry, Error, MasterKey, RecoveryKey, Share, Vault, VaultData};
+use serde::{Deserialize, Serialize};
+use std::{cell::OnceCell, fs, path::{Path, PathBuf}};
+use anyhow::{anyhow, Result};
+use rpassword::prompt_password;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use chrono::{Utc};
+use crossterm::event::{Event, KeyCode};
+use crossterm::{event};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use textwrap::wrap;
+use dplock::utils::{compute_wait_time, get_terminal_width, is_encrypted, parse_duration_secs, parse_expired_time, parse_remind_time, write_secret_file};
+use zeroize::Zeroizing;
+
+/// A custom field requested on the command line; `value` is prompted for when not given.
+pub struct FieldInput {
+    pub name: String,
+    pub secret: bool,
+    pub value: Option<String>,
+}
+
+/// Everything `Vault::add` needs besides the entry's name and username.
+pub struct AddOptions<'a> {
+    pub use_time: bool,
+    pub message: Option<&'a str>,
+    pub generate: Option<&'a str>,
+    /// Read the password from the first line of stdin instead of prompting.
+    pub password_stdin: bool,
+    pub totp: bool,
+    pub kind: Option<EntryKind>,
+    pub fields: Vec<FieldInput>,
+    pub tags: Vec<String>,
+}
+
+pub const SECRET_MASK: &str = "••••••••";
+
+/// Predicates `list` combines; an entry has to match all of them.
+#[derive(Default)]
+pub struct ListFilter<'a> {
+    /// Substring of the name or username.
+    pub text: Option<&'a str>,
+    /// Tags the entry must all have.
+    pub tags: Vec<String>,
+    /// Folder the name must lie in, e.g. `work/aws`.
+    pub folder: Option<&'a str>,
+}
+
+impl ListFilter<'_> {
+    fn matches(&self, name: &str, entry: &Entry) -> bool {
+        self.text.is_none_or(|f| name.contains(f) || entry.username.contains(f))
+            && self.tags.iter().all(|tag| entry.has_tag(&normalize_tag(tag)))
+            && self.folder.is_none_or(|folder| folder::contains(folder, name))
+    }
+}
+
+/// Everything `Vault::edit` can change. `None` leaves a value as it is.
+pub struct EditOptions<'a> {
+    pub index: Option<usize>,
+    pub username: Option<&'a str>,
+    /// Prompt for a new password.
+    pub password: bool,
+    pub generate: Option<&'a str>,
+    /// An empty message removes it.
+    pub message: Option<&'a str>,
+    /// A duration from now, or "none".
+    pub expires: Option<&'a str>,
+    /// A duration before the expiry, or "none".
+    pub remind: Option<&'a str>,
+    pub kind: Option<EntryKind>,
+    pub fields: Vec<FieldInput>,
+    pub remove_fields: Vec<String>,
+    pub tags: Vec<String>,
+    pub untags: Vec<String>,
+    pub editor: bool,
+}
+
+impl EditOptions<'_> {
+    fn has_changes(&self) -> bool {
+        self.username.is_some() || self.password || self.generate.is_some() || self.message.is_some()
+            || self.expires.is_some() || self.remind.is_some() || self.kind.is_some()
+            || !self.fields.is_empty() || !self.remove_fields.is_empty()
+            || !self.tags.is_empty() || !self.untags.is_empty()
+    }
+}
+
+/// The entry as shown by `edit --editor`: dates in RFC 3339 and the TOTP secret as an
+/// `otpauth://` URI, so everything can be changed by hand.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EditableEntry {
+    username: String,
+    password: String,
+    kind: EntryKind,
+    message: Option<String>,
+    expires: Option<String>,
+    remind: Option<String>,
+    totp: Option<String>,
+    fields: Vec<CustomField>,
+    tags: Vec<String>,
+}
+
+impl EditableEntry {
+    fn from_entry(name: &str, entry: &Entry) -> Self {
+        let date = |t: i64| chrono::DateTime::from_timestamp(t, 0)
+            .map(|d| d.with_timezone(&chrono::Local).to_rfc3339());
+        EditableEntry {
+            username: entry.username.clone(),
+            password: entry.password.clone(),
+            kind: entry.kind,
+            message: entry.message.clone(),
+            expires: entry.expired.and_then(date),
+            remind: entry.remind.and_then(date),
+            totp: entry.totp.as_ref().map(|t| t.to_uri(name, &entry.username)),
+            fields: entry.fields.clone(),
+            tags: entry.tags.clone(),
+        }
+    }
+
+    fn into_entry(self, original: &Entry) -> Result<Entry> {
+        let date = |value: Option<String>, what: &str| -> Result<Option<i64>> {
+            value.filter(|v| !v.trim().is_empty())
+                .map(|v| chrono::DateTime::parse_from_rfc3339(v.trim())
+                    .map(|d| d.timestamp())
+                    .map_err(|_| anyhow!("❌ Invalid {} date '{}'. Use RFC 3339, e.g: 2030-01-31T00:00:00Z", what, v)))
+                .transpose()
+        };
+        for (i, field) in self.fields.iter().enumerate() {
+            if self.fields[..i].iter().any(|other| other.name.eq_ignore_ascii_case(&field.name)) {
+                return Err(anyhow!("❌ Field '{}' appears more than once.", field.name));
+            }
+        }
+
+        let mut entry = Entry {
+            username: self.username,
+            password: original.password.clone(),
+            expired: date(self.expires, "expiry")?,
+            remind: date(self.remind, "remind")?,
+            created_at: original.created_at,
+            message: self.message.filter(|m| !m.is_empty()),
+            totp: self.totp.filter(|t| !t.trim().is_empty()).map(|t| Totp::parse(&t)).transpose()?,
+            kind: self.kind,
+            fields: self.fields,
+            history: original.history.clone(),
+            tags: Vec::new(),
+        };
+        entry.add_tags(&self.tags);
+        entry.set_password(self.password);
+        Ok(entry)
+    }
+}
+
+/// What `mv` does when the target name already has an entry with the same username.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConflictMode {
+    /// Move nothing.
+    Abort,
+    /// Leave conflicting entries under the source name.
+    Skip,
+    /// Move them anyway, next to the target's entries.
+    Merge,
+}
+
+impl ConflictMode {
+    pub fn parse(input: &str) -> Result<Self> {
+        match input {
+            "abort" => Ok(ConflictMode::Abort),
+            "skip" => Ok(ConflictMode::Skip),
+            "merge" => Ok(ConflictMode::Merge),
+            _ => Err(anyhow!("❌ Unknown conflict mode '{}'. Use abort, skip or merge.", input)),
+        }
+    }
+}
+
+/// How `get` matches the name it is given.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Subsequence match over names, usernames and tags, best first.
+    Fuzzy,
+    Exact,
+    Regex,
+}
+
+/// How `get`, `list` and `check-reminders` print their results.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(input: &str) -> Result<Self> {
+        match input {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(anyhow!("❌ Unknown output format '{}'. Use: text, json", input)),
+        }
+    }
+}
+
+/// One entry as printed by `get` and `list` with `--format json`. Field names are part of the
+/// interface; secrets are only included with `get --show`.
+#[derive(Serialize)]
+struct EntryRecord<'a> {
+    name: &'a str,
+    index: usize,
+    username: &'a str,
+    #[serde(rename = "type")]
+    kind: EntryKind,
+    tags: &'a [String],
+    message: Option<&'a str>,
+    created_at: i64,
+    expires_at: Option<i64>,
+    remind_at: Option<i64>,
+    totp: bool,
+    history_count: usize,
+    fields: Vec<FieldRecord<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct FieldRecord<'a> {
+    name: &'a str,
+    secret: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<&'a str>,
+}
+
+impl<'a> EntryRecord<'a> {
+    /// `index` is zero-based and printed one-based, like `--index`.
+    fn new(name: &'a str, index: usize, entry: &'a Entry, show: bool) -> Self {
+        EntryRecord {
+            name,
+            index: index + 1,
+            username: &entry.username,
+            kind: entry.kind,
+            tags: &entry.tags,
+            message: entry.message.as_deref(),
+            created_at: entry.created_at,
+            expires_at: entry.expired,
+            remind_at: entry.remind,
+            totp: entry.totp.is_some(),
+            history_count: entry.history.len(),
+            fields: entry.fields.iter()
+                .map(|f| FieldRecord { name: &f.name, secret: f.secret, value: (show || !f.secret).then_some(f.value.as_str()) })
+                .collect(),
+            password: show.then_some(entry.password.as_str()),
+        }
+    }
+
+    /// Keeps only the field `name`, or fails when the entry doesn't have it.
+    fn only_field(mut self, name: &str) -> Result<Self> {
+        self.fields.retain(|f| f.name.eq_ignore_ascii_case(name));
+        if self.fields.is_empty() {
+            return Err(anyhow!("❌ No field '{}' on {} (👤 {}).", name, self.name, self.username));
+        }
+        Ok(self)
+    }
+}
+
+fn keyring_error(e: keyring::Error) -> dplock::Error {
+    dplock::Error::Keyring(e.to_string())
+}
+
+fn wait_with_countdown(wait_minutes: u64) -> Result<()> {
+    for remaining in (1..=wait_minutes * 60).rev() {
+        eprint!("\r⏳ Please wait {} second(s)...", remaining);
+        io::stderr().flush()?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    eprintln!();
+    Ok(())
+}
+
+/// Starts the agent in the background unless one is running. Returns `false` if it already was.
+pub fn start_agent() -> Result<bool> {
+    if agent::is_running() {
+        return Ok(false);
+    }
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .args(["agent", "start", "--foreground"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    {
+        // Own process group, so closing the terminal doesn't take the agent down with it.
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command.spawn()?;
+
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while Instant::now() < deadline {
+        if agent::is_running() {
+            return Ok(true);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Err(anyhow!("❌ The agent did not start. Run 'dplock agent start --foreground' to see why"))
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Layout of `export --plain`; `import` ignores the warning.
+#[derive(Serialize)]
+struct PlainExport<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+    #[serde(flatten)]
+    data: &'a VaultData,
+}
+
+/// One invocation of the CLI: the vault it works on, how to print results and where the
+/// master password comes from. Commands print their results; the work is done by `dplock::Vault`.
+pub struct Session {
+    vault: Vault,
+    format: OutputFormat,
+    password_source: Option<PasswordSource>,
+    /// The password read from `password_source`; a descriptor can only be read once.
+    source_password: OnceCell<String>,
+}
+
+/// Overall result of `check-reminders`, reported to the shell as the exit code.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ReminderStatus {
+    NothingDue,
+    RemindersDue,
+    ExpiredPresent,
+}
+
+impl ReminderStatus {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ReminderStatus::NothingDue => 0,
+            ReminderStatus::RemindersDue => 20,
+            ReminderStatus::ExpiredPresent => 21,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReminderItem<'a> {
+    name: &'a str,
+    index: usize,
+    username: &'a str,
+    expires_at: Option<i64>,
+    remind_at: Option<i64>,
+}
+
+#[derive(Serialize, Default)]
+struct ReminderReport<'a> {
+    expired: Vec<ReminderItem<'a>>,
+    reminder_due: Vec<ReminderItem<'a>>,
+    upcoming: Vec<ReminderItem<'a>>,
+}
+
+impl Session {
+    pub fn new(vault_file: Option<PathBuf>, keyfile: Option<PathBuf>, format: OutputFormat, password_source: Option<PasswordSource>) -> Self {
+        let vault = Vault::open(vault_file.unwrap_or_else(Vault::default_path))
+            .on_lock_wait(|| eprintln!("⏳ Waiting for another dplock process to release the vault..."));
+        Self {
+            vault: match keyfile {
+                Some(keyfile) => vault.with_keyfile(keyfile),
+                None => vault,
+            },
+            format,
+            password_source,
+            source_password: OnceCell::new(),
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn vault(&self) -> &Vault {
+        &self.vault
+    }
+
+    fn vault_path(&self) -> &Path {
+        self.vault.path()
+    }
+
+    /// Removes a master password cached in the keyring by versions before the agent.
+    pub fn clear_master_from_keyring(&self) -> Result<()> {
+        let service = "dplock";
+        let username = self.vault_path().to_string_lossy();
+        let entry = keyring::Entry::new(service, &username).map_err(keyring_error)?;
+        let _ = entry.delete_password(); // ignore if not found
+        Ok(())
+    }
+    
+    fn save_attempts_to_keyring(&self, attempts: u32) -> Result<()> {
+        let service = "dplock_attempts";
+        let username = self.vault_path().to_string_lossy();
+        let entry = keyring::Entry::new(service, &username).map_err(keyring_error)?;
+        entry.set_password(&attempts.to_string()).map_err(keyring_error)?;
+        Ok(())
+    }
+
+    fn load_attempts_from_keyring(&self) -> Result<u32> {
+        let service = "dplock_attempts";
+        let username = self.vault_path().to_string_lossy();
+        let entry = keyring::Entry::new(service, &username).map_err(keyring_error)?;
+        match entry.get_password() {
+            Ok(attempts) => Ok(attempts.parse().unwrap_or(0)),
+            Err(keyring::Error::NoEntry) => Ok(0),
+            Err(e) => Err(keyring_error(e).into()),
+        }
+    }
+
+    fn clear_attempts_from_keyring(&self) -> Result<()> {
+        let service = "dplock_attempts";
+        let username = self.vault_path().to_string_lossy();
+        let entry = keyring::Entry::new(service, &username).map_err(keyring_error)?;
+        let _ = entry.delete_password(); // Ignore if not found
+        Ok(())
+    }
+
+    fn prompt_password(prompt: &str) -> Result<String> {
+        if !unlock::has_terminal() {
+            return Err(anyhow!("❌ No terminal to prompt for: {}", prompt.trim_end_matches([' ', ':'])));
+        }
+        prompt_password(prompt).map_err(|e| anyhow!("Failed to read password: {e}"))
+    }
+
+    /// The master password from `--password-fd`, `--password-file` or `DPLOCK_PASSWORD`, if one was given.
+    fn master_from_source(&self) -> Result<Option<String>> {
+        let Some(source) = &self.password_source else {
+            return Ok(None);
+        };
+        if let Some(password) = self.source_password.get() {
+            return Ok(Some(password.clone()));
+        }
+        let password = source.read()?;
+        Ok(Some(self.source_password.get_or_init(|| password).clone()))
+    }
+
+    /// The master password from a non-interactive source, or prompted for without caching.
+    fn read_master_password(&self, prompt: &str) -> Result<String> {
+        match self.master_from_source()? {
+            Some(password) => Ok(password),
+            None => Self::prompt_password(prompt),
+        }
+    }
+
+    /// A key for the vault: from the password source, from the agent, or from a prompted
+    /// password, which then also goes to the agent.
+    fn get_master_key(&self, prompt: &str) -> Result<MasterKey> {
+        // Scripts get exactly one attempt and no agent, which they have no use for.
+        if let Some(password) = self.master_from_source()? {
+            return self.vault.unlock(&password).map_err(|e| match e {
+                Error::WrongPassword => {
+                    let keyfile = if self.vault.keyfile().is_some() { " or keyfile" } else { "" };
+                    anyhow::Error::new(e).context(format!("❌ Incorrect master password from {}{}.", self.password_source.as_ref().unwrap(), keyfile))
+                }
+                e => e.into(),
+            });
+        }
+
+        if !self.vault.exists() {
+            return Err(Error::VaultNotFound(self.vault_path().to_path_buf()).into());
+        }
+        if let Some(master) = agent::is_enabled().then(|| self.vault.unlock_with_agent()).flatten() {
+            return Ok(master);
+        }
+
+        if !unlock::has_terminal() {
+            return Err(anyhow!("❌ No terminal to ask for the master password. Use --password-fd, --password-file or {}.", PASSWORD_ENV));
+        }
+
+        // The throttle is best-effort: without a keyring (e.g. a headless box without a secret
+        // service) failed attempts are simply not counted.
+        let mut attempts = self.load_attempts_from_keyring().unwrap_or(0);
+        if attempts > 5 {
+            let wait_time = compute_wait_time(attempts);
+            eprintln!("⏳ Too many failed attempts. Please wait {} minute(s) before trying again.", wait_time);
+            wait_with_countdown(wait_time)?;
+        }
+
+        loop {
+            let password = Zeroizing::new(Self::prompt_password(prompt)?);
+            match self.vault.unlock(&password) {
+                Ok(master) => {
+                    let _ = self.clear_attempts_from_keyring();
+                    let _ = self.clear_master_from_keyring(); // plaintext cache of older versions
+                    if agent::is_enabled() {
+                        if let Err(e) = start_agent().and_then(|_| self.vault.share_with_agent(&master).map_err(Into::into)) {
+                            eprintln!("⚠️  The key was not handed to the agent: {e}");
+                        }
+                    }
+                    return Ok(master);
+                }
+                Err(Error::WrongPassword) => {
+                    attempts += 1;
+                    let _ = self.save_attempts_to_keyring(attempts);
+                    if self.vault.keyfile().is_some() {
+                        eprintln!("❌ Incorrect password or keyfile. Please try again.");
+                    } else {
+                        eprintln!("❌ Incorrect password. Please try again.");
+                    }
+
+                    if attempts > 5 {
+                        let wait_time = compute_wait_time(attempts);
+                        eprintln!("⏳ Please wait {} minute(s) before trying again.", wait_time);
+                        wait_with_countdown(wait_time)?;
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn prompt_optional_expired_time(prompt: &str) -> Result<Option<i64>> {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let trimmed = input.trim();
+
+        if trimmed.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(parse_expired_time(trimmed)?))
+        }
+    }
+
+    fn prompt_optional_remind_time( expired: Option<i64>, prompt: &str) -> Result<Option<i64>> {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let trimmed = input.trim();
+
+        if trimmed.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(parse_remind_time(expired, trimmed)?))
+        }
+    }
+
+    /// Key for callers working on `VaultData` directly, such as the TUI and `run`.
+    pub fn unlock(&self) -> Result<MasterKey> {
+        self.get_master_key("🔐 Master password: ")
+    }
+
+    pub fn init(&self) -> Result<()> {
+        let _ = self.clear_master_from_keyring(); // plaintext cache of older versions
+        let path = self.vault_path();
+        if path.exists() {
+            println!("⚠️  Vault already exists at: {}", path.display());
+            if !self.confirm_overwrite()? {
+                println!("❌ Initialization cancelled.");
+                return Ok(());
+            }
+        }
+
+        let new_password = Zeroizing::new(self.read_master_password("Set new master password: ")?);
+        let _lock = self.vault.lock()?;
+        self.vault.save_with_password(&VaultData::default(), &new_password)?;
+        println!("🔐 Vault initialized!");
+        if let Some(keyfile) = self.vault.keyfile() {
+            println!("🔑 It can only be unlocked together with the keyfile {}. Keep a copy of it somewhere safe.", keyfile.display());
+        }
+        Ok(())
+    }
+
+    pub fn add(&self, name: &str, username: &str, options: AddOptions) -> Result<()> {
+        let kind = options.kind.unwrap_or_default();
+        // Template fields are only prompted for when a template was asked for explicitly.
+        let template_fields = if options.kind.is_some() { kind.fields() } else { &[] };
+        let policy = options.generate.map(Policy::parse).transpose()?;
+        for (i, input) in options.fields.iter().enumerate() {
+            let duplicate = template_fields.iter().any(|spec| spec.name.eq_ignore_ascii_case(&input.name))
+                || options.fields[..i].iter().any(|other| other.name.eq_ignore_ascii_case(&input.name));
+            if duplicate {
+                return Err(anyhow!("❌ Field '{}' is given more than once.", input.name));
+            }
+        }
+
+        let master = self.get_master_key("🔐 Master password: ")?;
+        // Checked before prompting, so a bad name doesn't throw away what was typed.
+        let name = self.vault.read(&master)?.name_for_add(name)?;
+        let name = name.as_str();
+        let entry_pass = match &policy {
+            Some(policy) => {
+                let generated = generator::generate(policy)?;
+                println!("🎲 Generated password: ~{:.0} bits of entropy ({}).", generated.entropy_bits, generator::strength_label(generated.entropy_bits));
+                generated.value
+            }
+            None if options.password_stdin => unlock::read_stdin_line().map_err(|e| anyhow!("❌ Could not read the password from stdin: {e}"))?,
+            None if kind == EntryKind::Login => Self::prompt_password(format!("🔑 '{username}' password: ").as_str())?,
+            None if kind.secret_required() => Self::prompt_password(format!("🔑 {}: ", kind.secret_label()).as_str())?,
+            None => Self::prompt_password(format!("🔑 {} (leave blank for none): ", kind.secret_label()).as_str())?,
+        };
+
+        let mut fields = Vec::new();
+        for spec in template_fields {
+            if let Some(value) = Self::prompt_field(spec)? {
+                fields.push(CustomField { name: spec.name.to_string(), value, secret: spec.secret });
+            }
+        }
+        fields.extend(Self::resolve_fields(options.fields)?);
+
+        let totp = if options.totp { Some(Self::prompt_totp()?) } else { None };
+
+        let mut expired = None;
+        let mut remind = None;
+
+        if options.use_time {
+            expired = Self::prompt_optional_expired_time("⌛ Expired (e.g: 8h, 3d, 1w, 2m, 3y) or leave blank: ")?;
+            remind = Self::prompt_optional_remind_time(expired, "🔔 Remind before (e.g: 8h, 3d, 1w, 2m, 3y) or leave blank: ")?;
+        }
+
+        let _lock = self.vault.lock()?;
+        let mut data = self.vault.load(&master)?;
+
+        let mut entry = Entry::new(username, entry_pass);
+        entry.expired = expired;
+        entry.remind = remind;
+        entry.message = options.message.map(|m| m.to_string());
+        entry.totp = totp;
+        entry.kind = kind;
+        entry.fields = fields;
+        entry.add_tags(&options.tags);
+
+        data.add(name, entry)?;
+        self.vault.save(&data, &master)?;
+        println!("✅ Entry added under: {}", name);
+        Ok(())
+    }
+
+
+    /// Prompts for a template field, asking again while a required field is left blank.
+    fn prompt_field(spec: &FieldSpec) -> Result<Option<String>> {
+        let optional = if spec.required { "" } else { " (optional)" };
+        loop {
+            let value = if spec.from_file {
+                let path = Self::prompt_line(format!("📄 {} file{}: ", spec.name, optional).as_str())?;
+                if path.is_empty() {
+                    String::new()
+                } else {
+                    let path = match path.strip_prefix("~/") {
+                        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+                        None => PathBuf::from(&path),
+                    };
+                    fs::read_to_string(&path)
+                        .map_err(|e| anyhow!("❌ Failed to read {}: {e}", path.display()))?
+                        .trim_end()
+                        .to_string()
+                }
+            } else if spec.secret {
+                Self::prompt_password(format!("🏷️  {}{}: ", spec.name, optional).as_str())?
+            } else {
+                Self::prompt_line(format!("🏷️  {}{}: ", spec.name, optional).as_str())?
+            };
+
+            if !value.is_empty() {
+                return Ok(Some(value));
+            }
+            if !spec.required {
+                return Ok(None);
+            }
+            println!("❌ {} is required.", spec.name);
+        }
+    }
+
+    /// Turns command-line field requests into fields, prompting for values that weren't given.
+    /// Fields left blank are dropped.
+    fn resolve_fields(inputs: Vec<FieldInput>) -> Result<Vec<CustomField>> {
+        let mut fields = Vec::new();
+        for input in inputs {
+            let value = match input.value {
+                Some(value) => value,
+                None if input.secret => Self::prompt_password(format!("🏷️  {}: ", input.name).as_str())?,
+                None => Self::prompt_line(format!("🏷️  {}: ", input.name).as_str())?,
+            };
+            if !value.is_empty() {
+                fields.push(CustomField { name: input.name, value, secret: input.secret });
+            }
+        }
+        Ok(fields)
+    }
+
+    fn prompt_line(prompt: &str) -> Result<String> {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        Ok(input.trim().to_string())
+    }
+
+    fn prompt_totp() -> Result<Totp> {
+        let input = Self::prompt_password("🔢 TOTP secret or otpauth:// URI: ")?;
+        Ok(Totp::parse(&input)?)
+    }
+
+    pub fn otp(&self, name: &str, username: Option<&str>, show: bool, set: bool, no_clear: bool) -> Result<()> {
+        let master = self.get_master_key("🔐 Master password: ")?;
+        let totp = if set { Some(Self::prompt_totp()?) } else { None };
+        let _lock = if set { self.vault.lock()? } else { self.vault.lock_shared()? };
+        let mut data = self.vault.load(&master)?;
+        let name = data.resolve_name(name);
+        let name = name.as_ref();
+
+        let Some(entry_list) = data.entries.get_mut(name) else {
+            return Err(anyhow!("❌ No entries found with name: '{}'", name));
+        };
+
+        let missing = if set {
+            format!("❌ No entries found matching username '{}' under '{}'", username.unwrap_or(""), name)
+        } else {
+            format!("❌ No TOTP secret stored under '{}'. Add one with: dplock otp {} --set", name, name)
+        };
+        let idx = Self::select_entry(name, entry_list, username, |e| set || e.totp.is_some(), &missing)?;
+        let entry = &mut entry_list[idx];
+
+        if let Some(totp) = totp {
+            entry.totp = Some(totp);
+            let updated_username = entry.username.clone();
+            self.vault.save(&data, &master)?;
+            println!("✅ TOTP secret saved for: {} (👤 {})", name, updated_username);
+            return Ok(());
+        }
+
+        let totp = entry.totp.as_ref().unwrap();
+        let (code, remaining) = totp.code_at(Utc::now().timestamp() as u64)?;
+        if show {
+            println!("🔢 {} (valid for {}s)", code, remaining);
+        } else {
+            let clears = Self::copy_to_clipboard(&code, no_clear)?;
+            eprintln!("📋 OTP code for {} (👤 {}) copied to clipboard! Valid for {}s.{}", name, entry.username, remaining, clears);
+        }
+        Ok(())
+    }
+
+    /// Index of the single entry in `entry_list` matching `username` and `filter`.
+    /// Fails with `missing`, or with the ambiguous usernames, otherwise.
+    pub fn select_entry(name: &str, entry_list: &[Entry], username: Option<&str>, filter: impl Fn(&Entry) -> bool, missing: &str) -> Result<usize> {
+        let matches: Vec<usize> = entry_list.iter().enumerate()
+            .filter(|(_, entry)| username.is_none_or(|u| entry.username.contains(u)) && filter(entry))
+            .map(|(i, _)| i)
+            .collect();
+
+        match matches.as_slice() {
+            [] => Err(anyhow!("{}", missing)),
+            [idx] => Ok(*idx),
+            _ => {
+                let usernames: Vec<&str> = matches.iter().map(|i| entry_list[*i].username.as_str()).collect();
+                Err(anyhow!("⚠️  Several entries match under '{}': {}. Specify a username.", name, usernames.join(", ")))
+            }
+        }
+    }
+
+    pub fn qr(&self, name: &str, username: Option<&str>, wifi: Option<&str>, svg: Option<&str>) -> Result<()> {
+        let master = self.get_master_key("🔐 Master password: ")?;
+        let data = {
+            let _lock = self.vault.lock_shared()?;
+            self.vault.load(&master)?
+        };
+        let name = data.resolve_name(name);
+        let name = name.as_ref();
+
+        let Some(entry_list) = data.entries.get(name) else {
+            return Err(anyhow!("❌ No entries found with name: '{}'", name));
+        };
+
+        let (payload, secret_kind) = if let Some(security) = wifi {
+            let missing = format!("❌ No entries found matching username '{}' under '{}'", username.unwrap_or(""), name);
+            let idx = Self::select_entry(name, entry_list, username, |_| true, &missing)?;
+            let entry = &entry_list[idx];
+            (qr::wifi_payload(&entry.username, &entry.password, security)?, "Wi-Fi password")
+        } else {
+            let missing = format!("❌ No TOTP secret stored under '{}'. Use --wifi to share a Wi-Fi entry instead.", name);
+            let idx = Self::select_entry(name, entry_list, username, |e| e.totp.is_some(), &missing)?;
+            let entry = &entry_list[idx];
+            (entry.totp.as_ref().unwrap().to_uri(name, &entry.username), "TOTP secret")
+        };
+
+        match svg {
+            Some(path) => println!("⚠️  This writes the {} for '{}' to '{}' as an unencrypted QR code.", secret_kind, name, path),
+            None => println!("⚠️  This shows the {} for '{}' on screen as a QR code.", secret_kind, name),
+        }
+        let confirm = Self::prompt_password("Type 'yes' to confirm: ")?;
+        if confirm.trim() != "yes" {
+            println!("❌ Cancelled.");
+            return Ok(());
+        }
+
+        match svg {
+            Some(path) => {
+                write_secret_file(Path::new(path), qr::to_svg(&payload)?.as_bytes())?;
+                println!("🖼️  QR code written to: {}", path);
+            }
+            None => print!("{}", qr::to_terminal(&payload)?),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str, username: Option<&str>, mode: MatchMode, field: Option<&str>, show: bool, no_clear: bool) -> Result<()> {
+        let password = self.get_master_key("Master password: ")?;
+        let _lock = self.vault.lock_shared()?;
+        let data = self.vault.load(&password)?;
+
+        if mode == MatchMode::Regex {
+            return Self::get_by_regex(&data, name, username, field, show, no_clear, self.format);
+        }
+        let name = data.resolve_name(name);
+        let name = name.as_ref();
+
+        // An exact name always wins over fuzzy matches.
+        let candidates = data.search(name, username, mode == MatchMode::Exact);
+
+        if candidates.is_empty() {
+            return Err(anyhow!("❌ No entries found matching: '{}'", name));
+        }
+
+        let chosen = if candidates.len() == 1 {
+            0
+        } else if picker::is_available() {
+            let items: Vec<String> = candidates.iter()
+                .map(|found| format!("{} (👤 {}){}", found.name, found.entry.username, Self::tags_suffix(found.entry)))
+                .collect();
+            match picker::pick(&format!("🔎 {} entries match '{}'", candidates.len(), name), &items)? {
+                Some(i) => i,
+                None => return Err(anyhow!("❌ Cancelled.")),
+            }
+        } else {
+            eprintln!("⚠️  {} entries match '{}':", candidates.len(), name);
+            for found in &candidates {
+                eprintln!("  • {} #{} (👤 {})", found.name, found.index + 1, found.entry.username);
+            }
+            return Err(anyhow!("❌ Narrow the search, add a username, or use --exact."));
+        };
+
+        let (key, i, entry) = (candidates[chosen].name, candidates[chosen].index, candidates[chosen].entry);
+        if self.format == OutputFormat::Json {
+            let record = EntryRecord::new(key, i, entry, show);
+            let record = match field {
+                Some(field) => record.only_field(field)?,
+                None => record,
+            };
+            return print_json(&[record]);
+        }
+        println!("🔐 {}", key);
+        Self::print_entry_info(entry, i, field, show, no_clear)
+    }
+
+    fn get_by_regex(data: &VaultData, name: &str, username: Option<&str>, field: Option<&str>, show: bool, no_clear: bool, format: OutputFormat) -> Result<()> {
+        let regex = regex::Regex::new(name).map_err(|e| anyhow!("Invalid regex: {e}"))?;
+
+        let matched = data.entries.iter()
+            .filter(|(key, _)| regex.is_match(key))
+            .collect::<Vec<_>>();
+
+        let mut found = 0;
+        let mut records = Vec::new();
+        for (entry_name, entries) in matched {
+            let filtered_entries: Vec<_> = entries.iter().enumerate()
+                .filter(|(_, entry)| username.is_none_or(|u| entry.username.contains(u)))
+                .collect();
+
+            if filtered_entries.is_empty() {
+                eprintln!("❌ No entries found matching username '{}' under '{}'", username.unwrap_or(""), entry_name);
+                continue;
+            }
+            found += filtered_entries.len();
+
+            if format == OutputFormat::Json {
+                for (i, entry) in filtered_entries {
+                    let record = EntryRecord::new(entry_name, i, entry, show);
+                    records.push(match field {
+                        Some(field) => record.only_field(field)?,
+                        None => record,
+                    });
+                }
+                continue;
+            }
+
+            println!("🔐 Found {} entr{} for: {}", filtered_entries.len(), if filtered_entries.len() > 1 { "ies" } else { "y" }, entry_name);
+            for (i, (_, entry)) in filtered_entries.iter().enumerate() {
+                Self::print_entry_info(entry, i, field, show, no_clear)?;
+            }
+        }
+
+        if found == 0 {
+            return Err(anyhow!("❌ No entries found matching: '{}'", name));
+        }
+        if format == OutputFormat::Json {
+            print_json(&records)?;
+        }
+        Ok(())
+    }
+
+    pub fn history(&self, name: &str, index: Option<usize>, restore: Option<usize>, show: bool) -> Result<()> {
+        let master = self.get_master_key("🔐 Master password: ")?;
+        let _lock = if restore.is_some() { self.vault.lock()? } else { self.vault.lock_shared()? };
+        let mut data = self.vault.load(&master)?;
+        let name = data.resolve_name(name);
+        let name = name.as_ref();
+
+        let Some(entry_list) = data.entries.get_mut(name) else {
+            return Err(anyhow!("❌ No entries found with name: '{}'", name));
+        };
+        let idx = Self::pick_index(name, entry_list, index)?;
+        let entry = &mut entry_list[idx];
+
+        if let Some(n) = restore {
+            if entry.history.is_empty() {
+                return Err(anyhow!("❌ No previous passwords for: {} (👤 {})", name, entry.username));
+            }
+            if n == 0 || n > entry.history.len() {
+                return Err(Error::Parse(format!("Invalid history number. Use: 1..{}", entry.history.len())).into());
+            }
+            let previous = entry.history.remove(n - 1);
+            entry.set_password(previous.password);
+            let username = entry.username.clone();
+            self.vault.save(&data, &master)?;
+            println!("⏪ Restored password #{} for: {} (👤 {}). The replaced password is now #1 in the history.", n, name, username);
+            return Ok(());
+        }
+
+        if entry.history.is_empty() {
+            println!("📭 No previous passwords for: {} (👤 {})", name, entry.username);
+            return Ok(());
+        }
+
+        println!("🕘 Previous passwords for: {} (👤 {})", name, entry.username);
+        let now = Utc::now().timestamp();
+        for (i, previous) in entry.history.iter().enumerate() {
+            let replaced = chrono::DateTime::from_timestamp(previous.replaced_at, 0)
+                .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            let password = if show { previous.password.as_str() } else { SECRET_MASK };
+            println!("{}. 🔑 {}  (replaced {}, {} day(s) ago)", i + 1, password, replaced, (now - previous.replaced_at) / 86400);
+        }
+        Ok(())
+    }
+
+    pub fn list(&self, filter: &ListFilter, sort: Option<&str>, tree: bool) -> Result<()> {
+        let password = self.get_master_key("Master password: ")?;
+        let _lock = self.vault.lock_shared()?;
+        let data = self.vault.load(&password)?;
+
+        let mut entries: Vec<_> = data.entries.iter().flat_map(|(name, entry_list)| {
+            entry_list.iter().map(move |entry| (name, entry))
+        }).collect();
+
+        Self::apply_filter_and_sort(&mut entries, filter, sort);
+
+        if self.format == OutputFormat::Json {
+            if sort.is_none() {
+                entries.sort_by_key(|(name, _)| *name);
+            }
+            let records: Vec<EntryRecord> = entries.iter()
+                .map(|(name, entry)| {
+                    // The entries borrow from `data`, so their address gives back the index.
+                    let index = data.entries[*name].iter().position(|e| std::ptr::eq(e, *entry)).unwrap_or_default();
+                    EntryRecord::new(name, index, entry, false)
+                })
+                .collect();
+            print_json(&records)?;
+        }
+        if entries.is_empty() {
+            return Err(anyhow!("📭 No matching entries found."));
+        }
+        if self.format == OutputFormat::Json {
+            return Ok(());
+        }
+
+        println!("📒 Entries:");
+        if tree {
+            let items: Vec<(&str, String)> = entries.iter()
+                .map(|(name, entry)| (name.as_str(), format!("(👤 {}){}", entry.username, Self::tags_suffix(entry))))
+                .collect();
+            for line in folder::tree_lines(&items) {
+                println!("{}", line);
+            }
+        } else {
+            Self::paginate_entries(entries)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str, index: Option<usize>) -> Result<()> {
+        let master = self.get_master_key("🔐 Master password: ")?;
+        let _lock = self.vault.lock()?;
+        let mut data = self.vault.load(&master)?;
+        let name = data.resolve_name(name);
+        let name = name.as_ref();
+
+        let count = data.get(name).len();
+        if count == 0 {
+            return Err(anyhow!("❌ Entry name not found."));
+        }
+        if let Some(idx) = index {
+            if idx == 0 || idx > count {
+                return Err(Self::invalid_index(count));
+            }
+            let removed = data.remove(name, Some(idx - 1))?;
+            println!("🗑️ Removed entry: {} (👤 {})", name, removed[0].username);
+        } else {
+            println!("⚠️  This will remove ALL {} entr{} under '{}'.", count, if count > 1 { "ies" } else { "y" }, name);
+
+            let confirm = Self::prompt_password("Type 'yes' to confirm: ")?;
+            if confirm.trim() != "yes" {
+                println!("❌ Cancelled.");
+                return Ok(());
+            }
+
+            data.remove(name, None)?;
+            println!("🗑️ All entries under '{}' removed.", name);
+        }
+
+        self.vault.save(&data, &master)?;
+        Ok(())
+    }
+
+    pub fn edit(&self, name: &str, options: EditOptions) -> Result<()> {
+        if options.editor {
+            return self.edit_in_editor(name, options.index);
+        }
+        if !options.has_changes() {
+            return Err(anyhow!("❌ Nothing to change. Use e.g: --username, --password, --message, --field, or --editor."));
+        }
+
+        let policy = options.generate.map(Policy::parse).transpose()?;
+        let expired = match options.expires {
+            Some("none") => Some(None),
+            Some(input) => Some(Some(parse_expired_time(input)?)),
+            None => None,
+        };
+
+        let master = self.get_master_key("🔐 Master password: ")?;
+        let password = match &policy {
+            Some(policy) => {
+                let generated = generator::generate(policy)?;
+                println!("🎲 Generated password: ~{:.0} bits of entropy ({}).", generated.entropy_bits, generator::strength_label(generated.entropy_bits));
+                Some(generated.value)
+            }
+            None if options.password => Some(Self::prompt_password("🔑 New password: ")?),
+            None => None,
+        };
+        let fields = Self::resolve_fields(options.fields)?;
+
+        let _lock = self.vault.lock()?;
+        let mut data = self.vault.load(&master)?;
+        let name = data.resolve_name(name);
+        let name = name.as_ref();
+
+        let Some(entry_list) = data.entries.get_mut(name) else {
+            return Err(anyhow!("❌ No entries found with name: '{}'", name));
+        };
+        let idx = Self::pick_index(name, entry_list, options.index)?;
+        let entry = &mut entry_list[idx];
+
+        if let Some(username) = options.username {
+            entry.username = username.to_string();
+        }
+        if let Some(password) = password {
+            entry.set_password(password);
+        }
+        if let Some(message) = options.message {
+            entry.message = (!message.is_empty()).then(|| message.to_string());
+        }
+        if let Some(kind) = options.kind {
+            entry.kind = kind;
+        }
+        if let Some(expired) = expired {
+            entry.expired = expired;
+            if expired.is_none() {
+                entry.remind = None; // a reminder is relative to the expiry
+            }
+        }
+        match options.remind {
+            Some("none") => entry.remind = None,
+            Some(input) => entry.remind = Some(parse_remind_time(entry.expired, input)?),
+            None => {}
+        }
+        for name in &options.remove_fields {
+            let before = entry.fields.len();
+            entry.fields.retain(|f| !f.name.eq_ignore_ascii_case(name));
+            if entry.fields.len() == before {
+                return Err(anyhow!("❌ No field '{}' on this entry.", name));
+            }
+        }
+        for field in fields {
+            match entry.fields.iter_mut().find(|f| f.name.eq_ignore_ascii_case(&field.name)) {
+                Some(existing) => *existing = field,
+                None => entry.fields.push(field),
+            }
+        }
+        entry.add_tags(&options.tags);
+        for tag in &options.untags {
+            let tag = normalize_tag(tag);
+            entry.tags.retain(|t| !t.eq_ignore_ascii_case(&tag));
+        }
+
+        let username = entry.username.clone();
+        self.vault.save(&data, &master)?;
+        println!("✅ Entry updated: {} (👤 {})", name, username);
+        Ok(())
+    }
+
+    /// Opens the entry as JSON in `$EDITOR`. The vault isn't locked while the editor is open,
+    /// so the save is refused if the entry changed in the meantime.
+    fn edit_in_editor(&self, name: &str, index: Option<usize>) -> Result<()> {
+        let master = self.get_master_key("🔐 Master password: ")?;
+        let (name, idx, original) = {
+            let _lock = self.vault.lock_shared()?;
+            let data = self.vault.load(&master)?;
+            let name = data.resolve_name(name).into_owned();
+            let Some(entry_list) = data.entries.get(&name) else {
+                return Err(anyhow!("❌ No entries found with name: '{}'", name));
+            };
+            let idx = Self::pick_index(&name, entry_list, index)?;
+            (name, idx, entry_list[idx].clone())
+        };
+        let name = name.as_str();
+
+        let mut text = serde_json::to_string_pretty(&EditableEntry::from_entry(name, &original))?;
+        let updated = loop {
+            text = editor::edit(&text)?;
+            let parsed = serde_json::from_str::<EditableEntry>(&text)
+                .map_err(|e| anyhow!("❌ Invalid entry: {e}"))
+                .and_then(|editable| editable.into_entry(&original));
+            match parsed {
+                Ok(entry) => break entry,
+                Err(e) => {
+                    println!("{}", e);
+                    if Self::prompt_line("Edit again? (yes/no): ")? != "yes" {
+                        println!("❌ Cancelled. Nothing was changed.");
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        if serde_json::to_value(&updated)? == serde_json::to_value(&original)? {
+            println!("ℹ️  No changes.");
+            return Ok(());
+        }
+
+        let _lock = self.vault.lock()?;
+        let mut data = self.vault.load(&master)?;
+        let current = data.entries.get_mut(name).and_then(|list| list.get_mut(idx));
+        match current {
+            Some(entry) if serde_json::to_value(&*entry)? == serde_json::to_value(&original)? => *entry = updated,
+            _ => return Err(Error::Conflict(format!("'{}' was changed by another dplock process while you were editing. Nothing was saved", name)).into()),
+        }
+
+        self.vault.save(&data, &master)?;
+        println!("✅ Entry updated: {}", name);
+        Ok(())
+    }
+
+    /// Zero-based position of the entry chosen with a 1-based `--index`, which may be left
+    /// out when the name holds a single entry. Lists the entries when it can't tell.
+    fn pick_index(name: &str, entry_list: &[Entry], index: Option<usize>) -> Result<usize> {
+        match index {
+            Some(i) if i >= 1 && i <= entry_list.len() => Ok(i - 1),
+            Some(_) => Err(Self::invalid_index(entry_list.len())),
+            None if entry_list.len() == 1 => Ok(0),
+            None => {
+                eprintln!("⚠️  '{}' has {} entries:", name, entry_list.len());
+                for (i, entry) in entry_list.iter().enumerate() {
+                    eprintln!("  {}. 👤 {}", i + 1, entry.username);
+                }
+                Err(anyhow!("❌ '{}' has {} entries. Pick one with --index.", name, entry_list.len()))
+            }
+        }
+    }
+
+    fn invalid_index(count: usize) -> anyhow::Error {
+        Error::Parse(format!("Invalid index. Use: 1..{}", count)).into()
+    }
+
+    /// Moves all entries under `source` (or only entry `index`) to `target`.
+    pub fn move_entries(&self, source: &str, target: &str, index: Option<usize>, on_conflict: ConflictMode) -> Result<()> {
+        let master = self.get_master_key("🔐 Master password: ")?;
+        let _lock = self.vault.lock()?;
+        let mut data = self.vault.load(&master)?;
+        let source = data.resolve_name(source);
+        let source = source.as_ref();
+        let target = data.name_for_add(target)?;
+        let target = target.as_str();
+        if source == target {
+            return Err(anyhow!("❌ Source and target are the same name: '{}'", source));
+        }
+
+        let Some(source_list) = data.entries.get(source) else {
+            return Err(anyhow!("❌ No entries found with name: '{}'", source));
+        };
+        let selected: Vec<usize> = match index {
+            Some(i) if i >= 1 && i <= source_list.len() => vec![i - 1],
+            Some(_) => return Err(Self::invalid_index(source_list.len())),
+            None => (0..source_list.len()).collect(),
+        };
+
+        let target_list = data.entries.get(target).map(Vec::as_slice).unwrap_or_default();
+        let conflicts: Vec<usize> = selected.iter().copied()
+            .filter(|&i| target_list.iter().any(|e| e.username == source_list[i].username))
+            .collect();
+
+        if !conflicts.is_empty() {
+            println!("⚠️  '{}' already has entries for:", target);
+            for &i in &conflicts {
+                println!("  👤 {}", source_list[i].username);
+            }
+            if on_conflict == ConflictMode::Abort {
+                return Err(Error::Conflict("Nothing moved. Use --on-conflict skip or --on-conflict merge".to_string()).into());
+            }
+        }
+
+        let to_move: Vec<usize> = selected.into_iter()
+            .filter(|i| on_conflict == ConflictMode::Merge || !conflicts.contains(i))
+            .collect();
+        if to_move.is_empty() {
+            println!("⏭️  Nothing moved: every entry conflicts with '{}'.", target);
+            return Ok(());
+        }
+
+        let source_list = data.entries.get_mut(source).unwrap();
+        let mut moved = Vec::with_capacity(to_move.len());
+        for &i in to_move.iter().rev() {
+            moved.push(source_list.remove(i));
+        }
+        moved.reverse();
+        if source_list.is_empty() {
+            data.entries.remove(source);
+        }
+        let count = moved.len();
+        data.entries.entry(target.to_string()).or_default().extend(moved);
+
+        self.vault.save(&data, &master)?;
+        println!("🚚 Moved {} entr{} from '{}' to '{}'.", count, if count > 1 { "ies" } else { "y" }, source, target);
+        if on_conflict == ConflictMode::Skip && !conflicts.is_empty() {
+            println!("⏭️  Skipped {} conflicting entr{}, left under '{}'.", conflicts.len(), if conflicts.len() > 1 { "ies" } else { "y" }, source);
+        }
+        Ok(())
+    }
+
+    pub fn passwd(&self, export_path: Option<&str>) -> Result<()> {
+        let old_master = self.read_master_password("🔐 Current master password: ")?;
+        let key = self.vault.unlock(&old_master).map_err(|e| match e {
+            Error::WrongPassword => anyhow::Error::new(e).context("❌ Wrong master password. Master password not changed."),
+            e => e.into(),
+        })?;
+
+        let new_master = Self::prompt_password("🔑 New master password: ")?;
+        let confirm = Self::prompt_password("🔑 Confirm new master password: ")?;
+        if new_master != confirm {
+            return Err(anyhow!("❌ Passwords do not match. Master password not changed."));
+        }
+
+        // Locked only now, so no other command waits while the passwords are typed.
+        let _lock = self.vault.lock()?;
+        let data = self.vault.load(&key)?;
+        self.vault.change_password(&data, &key, &new_master)?;
+        let _ = self.clear_master_from_keyring(); // plaintext cache of older versions
+        println!("🔐 Master password changed and vault re-encrypted.");
+
+        if let Some(path) = export_path {
+            Self::reencrypt_export(path, &old_master, &new_master)?;
+        }
+        Ok(())
+    }
+
+    fn reencrypt_export(path: &str, old_master: &str, new_master: &str) -> Result<()> {
+        let json = fs::read(path)?;
+        if !is_encrypted(&json) {
+            println!("⚠️  Export '{}' has no encrypted passwords. Nothing to re-encrypt.", path);
+            return Ok(());
+        }
+
+        let mut export_data: VaultData = serde_json::from_slice(&json)?;
+        for entry_list in export_data.entries.values_mut() {
+            for entry in entry_list.iter_mut() {
+                entry.map_secrets(|secret| -> Result<String> {
+                    let plain = crypto::decrypt_entry(secret, old_master)
+                        .map_err(|_| anyhow!("Export '{}' was not encrypted with the old master password", path))?;
+                    Ok(crypto::encrypt_entry(&plain, new_master)?)
+                })?;
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&export_data)?;
+        write_secret_file(Path::new(path), json.as_bytes())?;
+        println!("🔒 Export re-encrypted with the new master password: {}", path);
+        Ok(())
+    }
+
+    /// Splits a new recovery key into `shares` shares and lets it unwrap the vault key too, so
+    /// any `threshold` of them can reset the master password. An earlier kit stops working.
+    pub fn recovery_create(&self, shares: u8, threshold: u8, show_qr: bool, svg_dir: Option<&str>) -> Result<()> {
+        let recovery = RecoveryKey::generate();
+        let shares = recovery.split(shares, threshold)?;
+        if self.vault.has_recovery()? {
+            eprintln!("⚠️  This replaces the vault's recovery kit. Shares of the old kit stop working.");
+        }
+
+        // The kit wraps the key derived from the password, so the agent's copy won't do.
+        let password = Zeroizing::new(self.read_master_password("🔐 Master password: ")?);
+        let _lock = self.vault.lock()?;
+        let key = self.vault.unlock(&password)?;
+        let data = self.vault.load(&key)?;
+        self.vault.add_recovery(&data, &key, &recovery)?;
+        drop(_lock);
+
+        if let Some(dir) = svg_dir {
+            for share in &shares {
+                let path = Path::new(dir).join(format!("share-{}.svg", share.index()));
+                write_secret_file(&path, qr::to_svg(&share.to_words())?.as_bytes())?;
+            }
+        }
+
+        println!("🧩 Recovery kit created: any {} of these {} shares reset the master password.", threshold, shares.len());
+        println!("   Give each share to a different person or place, and keep them offline.");
+        for share in &shares {
+            println!("\n📜 Share {} of {}:", share.index(), shares.len());
+            let words = share.to_words();
+            let words: Vec<&str> = words.split(' ').collect();
+            for (line, chunk) in words.chunks(9).enumerate() {
+                println!("   {:>2}. {}", line * 9 + 1, chunk.join(" "));
+            }
+            if show_qr {
+                print!("{}", qr::to_terminal(&words.join(" "))?);
+            }
+        }
+        if let Some(dir) = svg_dir {
+            println!("\n🖼️  QR codes written to: {}", Path::new(dir).join("share-<N>.svg").display());
+        }
+        Ok(())
+    }
+
+    /// Reads recovery shares until there are enough, then sets a new master password.
+    /// Resets the master password with the shares of the recovery kit. The password options of
+    /// the session are not used: they would hold the forgotten password, not the new one.
+    pub fn recovery_restore(&self, new_password_file: Option<&str>, drop_keyfile: bool) -> Result<()> {
+        if !self.vault.has_recovery()? {
+            return Err(Error::Recovery("This vault has no recovery kit. Only a kit made with 'dplock recovery create' can reset the master password".to_string()).into());
+        }
+        let drops_keyfile = self.vault.requires_keyfile()? && self.vault.keyfile().is_none();
+        if drops_keyfile && !drop_keyfile {
+            return Err(Error::Keyfile("This vault requires a keyfile. Pass it with --keyfile to keep requiring it, or --drop-keyfile to reset the vault without one".to_string()).into());
+        }
+
+        let shares = Self::read_shares()?;
+        let recovery = RecoveryKey::combine(&shares)?;
+        let _lock = self.vault.lock()?;
+        let key = self.vault.unlock_with_recovery(&recovery)?;
+        let data = self.vault.load(&key)?;
+        eprintln!("✅ Shares accepted.");
+
+        let new_master = match new_password_file {
+            Some(path) => PasswordSource::File(PathBuf::from(path)).read()?,
+            None => {
+                let new_master = Self::prompt_password("🔑 New master password: ")?;
+                if new_master != Self::prompt_password("🔑 Confirm new master password: ")? {
+                    return Err(anyhow!("❌ Passwords do not match. Master password not changed."));
+                }
+                new_master
+            }
+        };
+        self.vault.change_password(&data, &key, &new_master)?;
+        let _ = self.clear_master_from_keyring(); // plaintext cache of older versions
+        println!("🔐 Master password reset. The recovery kit stays valid.");
+        if drops_keyfile {
+            println!("⚠️  The vault no longer requires a keyfile. Add one again with: dplock --keyfile <PATH> passwd");
+        }
+        Ok(())
+    }
+
+    /// Shares from stdin, `SHARE_WORDS` words each over one or more lines, until the
+    /// threshold of the first one is reached.
+    fn read_shares() -> Result<Vec<Share>> {
+        let interactive = io::stdin().is_terminal();
+        if interactive {
+            eprintln!("🧩 Enter the recovery shares ({} words each).", SHARE_WORDS);
+        }
+        let mut shares: Vec<Share> = Vec::new();
+        let mut words = Zeroizing::new(String::new());
+        loop {
+            let needed = shares.first().map_or(0, |share| share.threshold() as usize);
+            if needed > 0 && shares.len() >= needed {
+                return Ok(shares);
+            }
+            if interactive {
+                match (words.split_whitespace().count(), needed) {
+                    (0, 0) => eprint!("Share 1: "),
+                    (0, _) => eprint!("Share {} of {}: ", shares.len() + 1, needed),
+                    (count, _) => eprint!("   ({} of {} words) ", count, SHARE_WORDS),
+                }
+                io::stderr().flush()?;
+            }
+            let mut line = Zeroizing::new(String::new());
+            if io::stdin().read_line(&mut line)? == 0 {
+                // Not enough shares; `combine` says how many are missing.
+                return Ok(shares);
+            }
+            words.push_str(&line);
+            words.push(' ');
+            if words.split_whitespace().count() < SHARE_WORDS {
+                continue;
+            }
+            let parsed = Share::from_words(&words);
+            words.clear();
+            match parsed {
+                Ok(share) if interactive && shares.iter().any(|other| other.index() == share.index()) => {
+                    eprintln!("⚠️  Share {} was already entered. Please enter another one.", share.index());
+                }
+                Ok(share) => shares.push(share),
+                Err(e) if interactive => eprintln!("❌ {}. Please enter this share again.", e),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    pub fn list_backups(&self) -> Result<()> {
+        let path = self.vault_path();
+        let _lock = self.vault.lock_shared()?;
+        let mut found = false;
+
+        for n in self.vault.backup_numbers()? {
+            let Ok(metadata) = fs::metadata(self.vault.backup_path(n)) else { continue };
+            if !found {
+                println!("🗄️  Backups of {}:", path.display());
+                found = true;
+            }
+            let modified = metadata.modified()
+                .map(|t| chrono::DateTime::<chrono::Local>::from(t).format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|_| "unknown".to_string());
+            println!("{}. {} ({} bytes)", n, modified, metadata.len());
+        }
+
+        if !found {
+            println!("📭 No backups found for: {}", path.display());
+        }
+        Ok(())
+    }
+
+    pub fn restore_backup(&self, n: usize) -> Result<()> {
+        let backup = self.vault.backup_path(n);
+        if n == 0 || !backup.exists() {
+            return Err(anyhow!("❌ Backup {} not found. Run 'dplock backups list' to see available backups.", n));
+        }
+
+        let master = self.get_master_key("🔐 Master password: ")?;
+        let _lock = self.vault.lock()?;
+        // Backups written before the last `passwd` or `recovery create` carry the old key header;
+        // restoring one would silently bring back the old password, so they are refused.
+        let data = self.vault.load_backup(n, &master).map_err(|e| match e {
+            Error::WrongPassword => anyhow::Error::new(e).context(format!(
+                "❌ Backup {} was saved before the master password, keyfile or recovery kit changed. Restoring it would bring the old ones back, so nothing was restored.", n)),
+            e => e.into(),
+        })?;
+
+        let count: usize = data.entries.values().map(Vec::len).sum();
+        println!("⚠️  This will replace the current vault with backup {} ({} entr{}).", n, count, if count == 1 { "y" } else { "ies" });
+        let confirm = Self::prompt_password("Type 'yes' to confirm: ")?;
+        if confirm.trim() != "yes" {
+            println!("❌ Cancelled.");
+            return Ok(());
+        }
+
+        self.vault.restore_backup(n)?;
+        println!("✅ Vault restored from backup {}. The previous vault was kept as backup 1.", n);
+        Ok(())
+    }
+
+    fn confirm_overwrite(&self) -> Result<bool> {
+        let confirm = prompt_password("Do you want to overwrite it? Type 'yes' to confirm: ")?;
+        if confirm.trim() != "yes" {
+            return Ok(false);
+        }
+
+        let old_password = prompt_password("Enter current master password: ")?;
+        match self.vault.unlock(&old_password) {
+            Ok(_) => {
+                println!("✅ Password confirmed.");
+                Ok(true)
+            }
+            Err(_) => {
+                println!("❌ Wrong master password. Vault not overwritten.");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Copies `text` and returns a note like " Clears in 30s." for the confirmation message.
+    fn copy_to_clipboard(text: &str, no_clear: bool) -> Result<String> {
+        Ok(match clipboard::copy(text, no_clear)? {
+            Some(secs) => format!(" Clears in {}s.", secs),
+            None => String::new(),
+        })
+    }
+
+    fn apply_filter_and_sort(
+        entries: &mut Vec<(&String, &Entry)>,
+        filter: &ListFilter,
+        sort: Option<&str>,
+    ) {
+        entries.retain(|(name, entry)| filter.matches(name, entry));
+
+        if let Some(sort_key) = sort {
+            match sort_key {
+                "name" => entries.sort_by_key(|(name, _)| *name),
+                "username" => entries.sort_by_key(|(_, entry)| &entry.username),
+                _ => {}
+            }
+        }
+    }
+
+    fn tags_suffix(entry: &Entry) -> String {
+        entry.tags.iter().map(|t| format!(" #{}", t)).collect()
+    }
+
+    fn paginate_entries(entries: Vec<(&String, &Entry)>) -> Result<()> {
+        let term_width = get_terminal_width().saturating_sub(2);
+        let page_size = 10;
+
+        for (i, (name, entry)) in entries.iter().enumerate() {
+            let mut expired_noti = String::new();
+            let mut remind_noti= String::new();
+            let mut message= String::new();
+
+            if let Some(exp) = entry.expired {
+                let now = Utc::now().timestamp();
+                let diff = exp - now;
+                if diff <= 0 {
+                    expired_noti = format!("\n   ⚠️  Password expired {} day(s) ago.", diff.abs() / 86400);
+                } else {
+                    expired_noti = format!("\n   ⏰ Expires in {} day(s).", diff / 86400);
+                }
+            }
+            if let Some(remind) = entry.remind {
+                let now = Utc::now().timestamp();
+                if remind <= now {
+                    remind_noti = "\n   🔔 Reminder: This password should be reviewed!".to_string();
+                } else {
+                    let diff = remind - now;
+                    remind_noti = format!("\n   🔔 Reminder in {} day(s).", diff / 86400);
+                }
+            }
+            if let Some(msg) = &entry.message {
+                message = format!("\n   📝 Message: {}", msg);
+            }
+            let fields: String = entry.fields.iter()
+                .map(|f| format!("\n   🏷️  {}: {}", f.name, if f.secret { SECRET_MASK } else { &f.value }))
+                .collect();
+
+            let line = format!("• {} (👤 {}){}{}{}{}{}", name, entry.username, Self::tags_suffix(entry), expired_noti, remind_noti, message, fields);
+
+            for wrapped in wrap(&line, term_width) {
+                println!("{}", wrapped);
+            }
+
+            if (i + 1) % page_size == 0 && i + 1 < entries.len() {
+                print!("-- More (press any key to continue, q to quit) -- ");
+                io::stdout().flush()?;
+
+                enable_raw_mode()?;
+                let should_quit = if let Event::Key(key_event) = event::read()? {
+                    matches!(key_event.code, KeyCode::Char('q'))
+                } else {
+                    false
+                };
+                disable_raw_mode()?;
+
+                if should_quit {
+                    println!();
+                    break;
+                }
+
+                print!("\r{}\r", " ".repeat(60));
+                io::stdout().flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+
+    pub fn export(&self, path: &str, plain: bool) -> Result<()> {
+        let master = Zeroizing::new(self.read_master_password("🔐 Master password: ")?);
+        let _lock = self.vault.lock_shared()?;
+        let data = self.vault.load(&self.vault.unlock(&master)?)?;
+
+        if plain {
+            let previous: usize = data.entries.values().flatten().map(|e| e.history.len()).sum();
+            let export = PlainExport {
+                warning: (previous > 0).then(|| format!("PLAINTEXT: besides current passwords, the 'history' of each entry holds {} previous password(s) in clear text.", previous)),
+                data: &data,
+            };
+            write_secret_file(Path::new(path), serde_json::to_string_pretty(&export)?.as_bytes())?;
+            println!("📄 Vault exported as plain JSON: {}", path);
+            if previous > 0 {
+                println!("⚠️  The export includes {} previous password(s) from entry history in plain text.", previous);
+            }
+        } else {
+            let mut safe_data = data.clone();
+
+            for entry_list in safe_data.entries.values_mut() {
+                for entry in entry_list.iter_mut() {
+                    entry.map_secrets(|secret| crypto::encrypt_entry(secret, &master))?;
+                }
+            }
+
+            let json = serde_json::to_string_pretty(&safe_data)?;
+            write_secret_file(Path::new(path), json.as_bytes())?;
+            println!("🔒 Vault exported (passwords encrypted) to: {}", path);
+        }
+
+        Ok(())
+    }
+
+    pub fn import(&self, path: &str, plain: bool) -> Result<()> {
+        let json = std::fs::read(path)?;
+
+        if is_encrypted(&json) && plain {
+            println!("⚠️ Warning: The file appears to be encrypted, but you are attempting to import it as plain text.");
+            let confirm = prompt_password("Do you want to proceed with plain text import? (yes/no): ")?;
+            if confirm.trim().to_lowercase() != "yes" {
+                println!("❌ Import cancelled.");
+                return Ok(());
+            }
+        }
+
+        let imported_data: VaultData = serde_json::from_slice(&json)?;
+
+        let source_master = if !plain && is_encrypted(&json) {
+            Self::prompt_password("🔐 Source vault master password: ")?
+        } else {
+            String::new()
+        };
+
+        let target_vault_path = self.vault_path().to_path_buf();
+
+        let target_master = self.read_master_password("🔐 Target vault master password: ")?;
+        let _lock = self.vault.lock()?;
+
+        let target_key = self.vault.unlock(&target_master)?;
+        let mut current_data = self.vault.load(&target_key)?;
+
+        for (name, new_entries) in imported_data.entries {
+            let entry_list = current_data.entries.entry(name.clone()).or_default();
+            for mut new_entry in new_entries {
+                if !plain {
+                    new_entry.map_secrets(|secret| crypto::decrypt_entry(secret, &source_master))?;
+                }
+                if let Some(totp) = &new_entry.totp {
+                    totp.validate().map_err(|e| anyhow!("❌ {} / {}: {}", name, new_entry.username, e))?;
+                }
+                let is_duplicate = entry_list.iter().any(|e| {
+                    e.username == new_entry.username && e.password == new_entry.password
+                });
+                if !is_duplicate {
+                    entry_list.push(new_entry);
+                } else {
+                    println!("⚠️  Duplicate entry skipped: {} / {}", name, new_entry.username);
+                }
+            }
+        }
+
+        self.vault.save(&current_data, &target_key)?;
+        println!("✅ Vault imported and merged successfully into: {}", target_vault_path.display());
+        Ok(())
+    }
+
+    fn print_entry_info(entry: &Entry, index: usize, field: Option<&str>, show_password: bool, no_clear: bool) -> Result<()> {
+        println!("{}. 👤 Username: {}", index + 1, entry.username);
+
+        if let Some(field) = field {
+            return Self::print_field(entry, field, show_password, no_clear);
+        }
+
+        if entry.kind != EntryKind::Login {
+            println!("   🗂️  Type: {}", entry.kind.as_str());
+        }
+
+        if let Some(exp) = entry.expired {
+            let now = Utc::now().timestamp();
+            let diff = exp - now;
+
+            if diff <= 0 {
+                println!("   ⚠️  Password expired {} day(s) ago.", diff.abs() / 86400);
+            } else {
+                println!("   ⏰ Expires in {} day(s).", diff / 86400);
+            }
+        }
+
+        if let Some(remind) = entry.remind {
+            let now = Utc::now().timestamp();
+            if remind <= now {
+                println!("   🔔 Reminder: This password should be reviewed!");
+            } else {
+                let diff = remind - now;
+                println!("   🔔 Reminder in {} day(s).", diff / 86400);
+            }
+        }
+
+        if !entry.tags.is_empty() {
+            println!("   🔖 Tags: {}", entry.tags.join(", "));
+        }
+
+        if let Some(message) = &entry.message {
+            println!("   📝 Message: {}", message);
+        }
+
+        if entry.totp.is_some() {
+            println!("   🔢 TOTP: configured (use 'dplock otp' to get a code)");
+        }
+
+        if !entry.history.is_empty() {
+            println!("   🕘 History: {} previous password(s) (use 'dplock history' to view)", entry.history.len());
+        }
+
+        for field in &entry.fields {
+            let value = if show_password || !field.secret { field.value.as_str() } else { SECRET_MASK };
+            println!("   🏷️  {}: {}", field.name, value);
+        }
+
+        let label = entry.kind.secret_label();
+        if entry.password.is_empty() && !entry.kind.secret_required() {
+            println!("   🔑 {}: (none)", label);
+        } else if show_password {
+            println!("   🔑 {}: {}", label, entry.password);
+        } else {
+            let clears = Self::copy_to_clipboard(&entry.password, no_clear)?;
+            eprintln!("   📋 {} copied to clipboard!{}", label, clears);
+        }
+
+        Ok(())
+    }
+
+    fn print_field(entry: &Entry, name: &str, show: bool, no_clear: bool) -> Result<()> {
+        let Some(field) = entry.field(name) else {
+            return Err(anyhow!("❌ No field '{}' on this entry.", name));
+        };
+        if show {
+            println!("   🏷️  {}: {}", field.name, field.value);
+        } else {
+            let clears = Self::copy_to_clipboard(&field.value, no_clear)?;
+            eprintln!("   📋 {} copied to clipboard!{}", field.name, clears);
+        }
+        Ok(())
+    }
+    pub fn check_reminders(&self, within: Option<&str>, json: bool) -> Result<ReminderStatus> {
+        let window = match within {
+            Some(input) => match parse_duration_secs(input)? {
+                0 => return Err(anyhow!("❌ Invalid --within format. Use e.g: 3h, 1d, 2w, 3m, 4y")),
+                secs => secs,
+            },
+            None => 0,
+        };
+
+        let password = self.get_master_key("Master password: ")?;
+        let _lock = self.vault.lock_shared()?;
+        let data = self.vault.load(&password)?;
+
+        let now = Utc::now().timestamp();
+        let horizon = now + window;
+        let mut report = ReminderReport::default();
+
+        let mut entries: Vec<_> = data.entries.iter().flat_map(|(name, entry_list)| {
+            entry_list.iter().enumerate().map(move |(index, entry)| (name, index, entry))
+        }).collect();
+        entries.sort_by(|(a_name, _, a), (b_name, _, b)| a_name.cmp(b_name).then(a.username.cmp(&b.username)));
+
+        for (name, index, entry) in entries {
+            let item = ReminderItem {
+                name,
+                index: index + 1, // one-based, like `--index`
+                username: &entry.username,
+                expires_at: entry.expired,
+                remind_at: entry.remind,
+            };
+            if entry.expired.is_some_and(|exp| exp <= now) {
+                report.expired.push(item);
+            } else if entry.remind.is_some_and(|remind| remind <= now) {
+                report.reminder_due.push(item);
+            } else if window > 0 && [entry.expired, entry.remind].iter().flatten().any(|t| *t <= horizon) {
+                report.upcoming.push(item);
+            }
+        }
+
+        let status = if !report.expired.is_empty() {
+            ReminderStatus::ExpiredPresent
+        } else if !report.reminder_due.is_empty() || !report.upcoming.is_empty() {
+            ReminderStatus::RemindersDue
+        } else {
+            ReminderStatus::NothingDue
+        };
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(status);
+        }
+
+        // The text report goes to stderr, so cron mails it while scripts only see the exit code.
+        if status == ReminderStatus::NothingDue {
+            eprintln!("✅ No passwords are due for review.");
+            return Ok(status);
+        }
+
+        Self::print_reminder_group("⚠️  Expired", &report.expired, now);
+        Self::print_reminder_group("🔔 Reminder due", &report.reminder_due, now);
+        Self::print_reminder_group(&format!("⏰ Upcoming within {}", within.unwrap_or_default().trim()), &report.upcoming, now);
+        Ok(status)
+    }
+
+    fn print_reminder_group(title: &str, items: &[ReminderItem], now: i64) {
+        if items.is_empty() {
+            return;
+        }
+        eprintln!("{} ({}):", title, items.len());
+        for item in items {
+            let mut details = Vec::new();
+            if let Some(exp) = item.expires_at {
+                let diff = exp - now;
+                if diff <= 0 {
+                    details.push(format!("expired {} day(s) ago", diff.abs() / 86400));
+                } else {
+                    details.push(format!("expires in {} day(s)", diff / 86400));
+                }
+            }
+            if let Some(remind) = item.remind_at {
+                if remind > now {
+                    details.push(format!("reminder in {} day(s)", (remind - now) / 86400));
+                }
+            }
+            eprintln!("   • {} (👤 {}) — {}", item.name, item.username, details.join(", "));
+        }
+    }
+}
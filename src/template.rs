@@ -0,0 +1,84 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// What an entry stores. Decides the label of its main secret and the fields `add` prompts for.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum EntryKind {
+    #[default]
+    Login,
+    ApiKey,
+    Card,
+    SshKey,
+    Note,
+}
+
+/// A field a template asks for, in addition to the username and main secret.
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub secret: bool,
+    pub required: bool,
+    /// Ask for a file path and store the file's contents (e.g. SSH keys).
+    pub from_file: bool,
+}
+
+const fn field(name: &'static str, secret: bool, required: bool) -> FieldSpec {
+    FieldSpec { name, secret, required, from_file: false }
+}
+
+const LOGIN_FIELDS: &[FieldSpec] = &[field("url", false, false)];
+const API_KEY_FIELDS: &[FieldSpec] = &[field("url", false, false), field("secret", true, false)];
+const CARD_FIELDS: &[FieldSpec] = &[field("expiry", false, true), field("cvv", true, true), field("pin", true, false)];
+const SSH_KEY_FIELDS: &[FieldSpec] = &[
+    FieldSpec { name: "private key", secret: true, required: true, from_file: true },
+    FieldSpec { name: "public key", secret: false, required: false, from_file: true },
+];
+
+impl EntryKind {
+    pub fn parse(input: &str) -> Result<Self> {
+        match input.to_lowercase().replace('_', "-").as_str() {
+            "login" => Ok(EntryKind::Login),
+            "api-key" | "apikey" | "api" => Ok(EntryKind::ApiKey),
+            "card" | "credit-card" => Ok(EntryKind::Card),
+            "ssh-key" | "ssh" => Ok(EntryKind::SshKey),
+            "note" | "secure-note" => Ok(EntryKind::Note),
+            _ => Err(Error::Parse(format!("Unknown template '{}'. Use one of: login, api-key, card, ssh-key, note", input))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::Login => "login",
+            EntryKind::ApiKey => "api-key",
+            EntryKind::Card => "card",
+            EntryKind::SshKey => "ssh-key",
+            EntryKind::Note => "note",
+        }
+    }
+
+    /// Label of the main secret, which is kept in `Entry::password`.
+    pub fn secret_label(self) -> &'static str {
+        match self {
+            EntryKind::Login => "Password",
+            EntryKind::ApiKey => "API key",
+            EntryKind::Card => "Card number",
+            EntryKind::SshKey => "Passphrase",
+            EntryKind::Note => "Note",
+        }
+    }
+
+    /// An SSH key may have no passphrase; every other kind needs its main secret.
+    pub fn secret_required(self) -> bool {
+        self != EntryKind::SshKey
+    }
+
+    pub fn fields(self) -> &'static [FieldSpec] {
+        match self {
+            EntryKind::Login => LOGIN_FIELDS,
+            EntryKind::ApiKey => API_KEY_FIELDS,
+            EntryKind::Card => CARD_FIELDS,
+            EntryKind::SshKey => SSH_KEY_FIELDS,
+            EntryKind::Note => &[],
+        }
+    }
+}
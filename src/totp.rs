@@ -0,0 +1,248 @@
+use crate::error::{Error, Result};
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+const BASE32: Alphabet = Alphabet::Rfc4648 { padding: false };
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl TotpAlgorithm {
+    fn parse(input: &str) -> Result<Self> {
+        match input.to_uppercase().replace('-', "").as_str() {
+            "SHA1" => Ok(TotpAlgorithm::Sha1),
+            "SHA256" => Ok(TotpAlgorithm::Sha256),
+            "SHA512" => Ok(TotpAlgorithm::Sha512),
+            _ => Err(Error::Parse(format!("Unsupported TOTP algorithm: {}", input))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TotpAlgorithm::Sha1 => "SHA1",
+            TotpAlgorithm::Sha256 => "SHA256",
+            TotpAlgorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+/// RFC 6238 parameters for an entry. `secret` is stored as unpadded upper-case base32; in an
+/// encrypted export it holds ciphertext instead, so deserializing doesn't validate it. Use
+/// `validate` once it's decrypted.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Totp {
+    pub secret: String,
+    pub digits: u32,
+    pub period: u64,
+    pub algorithm: TotpAlgorithm,
+}
+
+impl Totp {
+    /// Accepts either an `otpauth://totp/...` URI or a bare base32 secret (spaces and padding ignored).
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        if input.to_lowercase().starts_with("otpauth://") {
+            Self::parse_uri(input)
+        } else {
+            Self::from_secret(input)
+        }
+    }
+
+    fn from_secret(secret: &str) -> Result<Self> {
+        let totp = Totp {
+            secret: normalize_secret(secret),
+            digits: 6,
+            period: 30,
+            algorithm: TotpAlgorithm::Sha1,
+        };
+        totp.validate()?;
+        Ok(totp)
+    }
+
+    fn parse_uri(uri: &str) -> Result<Self> {
+        let rest = &uri["otpauth://".len()..];
+        let (kind, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        if !kind.eq_ignore_ascii_case("totp") {
+            return Err(Error::Parse(format!("Only otpauth://totp URIs are supported (got '{}')", kind)));
+        }
+        let query = rest.split_once('?').map(|(_, q)| q).unwrap_or("");
+
+        let mut totp = Totp {
+            secret: String::new(),
+            digits: 6,
+            period: 30,
+            algorithm: TotpAlgorithm::Sha1,
+        };
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key.to_lowercase().as_str() {
+                "secret" => totp.secret = normalize_secret(&percent_decode(value)),
+                "digits" => totp.digits = value.parse().map_err(|_| Error::Parse(format!("Invalid TOTP digits: {}", value)))?,
+                "period" => totp.period = value.parse().map_err(|_| Error::Parse(format!("Invalid TOTP period: {}", value)))?,
+                "algorithm" => totp.algorithm = TotpAlgorithm::parse(value)?,
+                _ => {} // issuer, image, ... are not needed to compute codes
+            }
+        }
+        totp.validate()?;
+        Ok(totp)
+    }
+
+    /// Checks the parameters, e.g. of an imported entry; `code_at` fails on the same errors.
+    pub fn validate(&self) -> Result<()> {
+        if self.secret.is_empty() || base32::decode(BASE32, &self.secret).is_none() {
+            return Err(Error::Parse("Invalid TOTP secret: expected a base32 string".to_string()));
+        }
+        if !(6..=8).contains(&self.digits) {
+            return Err(Error::Parse("TOTP digits must be between 6 and 8".to_string()));
+        }
+        if self.period == 0 {
+            return Err(Error::Parse("TOTP period must be greater than 0".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Builds an `otpauth://` URI that authenticator apps can enroll, labelled `issuer:account`.
+    pub fn to_uri(&self, issuer: &str, account: &str) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+            percent_encode(issuer),
+            percent_encode(account),
+            self.secret,
+            percent_encode(issuer),
+            self.algorithm.as_str(),
+            self.digits,
+            self.period
+        )
+    }
+
+    /// Returns the code for `unix_time` (seconds) and the seconds left until it rolls over.
+    pub fn code_at(&self, unix_time: u64) -> Result<(String, u64)> {
+        self.validate()?; // the fields are public
+        let key = base32::decode(BASE32, &self.secret)
+            .ok_or_else(|| Error::Parse("Invalid TOTP secret: expected a base32 string".to_string()))?;
+        let counter = (unix_time / self.period).to_be_bytes();
+
+        let digest = match self.algorithm {
+            TotpAlgorithm::Sha1 => hmac_digest::<Hmac<Sha1>>(&key, &counter)?,
+            TotpAlgorithm::Sha256 => hmac_digest::<Hmac<Sha256>>(&key, &counter)?,
+            TotpAlgorithm::Sha512 => hmac_digest::<Hmac<Sha512>>(&key, &counter)?,
+        };
+
+        // Dynamic truncation (RFC 4226, section 5.3).
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = binary % 10u32.pow(self.digits);
+        let remaining = self.period - unix_time % self.period;
+        Ok((format!("{:0width$}", code, width = self.digits as usize), remaining))
+    }
+}
+
+fn hmac_digest<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = <M as Mac>::new_from_slice(key).map_err(|e| Error::Other(format!("HMAC error: {e}")))?;
+    mac.update(message);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn normalize_secret(secret: &str) -> String {
+    secret.chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+fn percent_encode(input: &str) -> String {
+    input.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rfc_totp(algorithm: TotpAlgorithm) -> Totp {
+        let seed: &[u8] = match algorithm {
+            TotpAlgorithm::Sha1 => b"12345678901234567890",
+            TotpAlgorithm::Sha256 => b"12345678901234567890123456789012",
+            TotpAlgorithm::Sha512 => b"1234567890123456789012345678901234567890123456789012345678901234",
+        };
+        Totp { secret: base32::encode(BASE32, seed), digits: 8, period: 30, algorithm }
+    }
+
+    /// Appendix B of RFC 6238.
+    #[test]
+    fn rfc_6238_vectors() {
+        let vectors = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, sha1, sha256, sha512) in vectors {
+            assert_eq!(rfc_totp(TotpAlgorithm::Sha1).code_at(time).unwrap().0, sha1);
+            assert_eq!(rfc_totp(TotpAlgorithm::Sha256).code_at(time).unwrap().0, sha256);
+            assert_eq!(rfc_totp(TotpAlgorithm::Sha512).code_at(time).unwrap().0, sha512);
+        }
+        assert_eq!(rfc_totp(TotpAlgorithm::Sha1).code_at(59).unwrap().1, 1);
+    }
+
+    #[test]
+    fn parses_uri_and_bare_secret() {
+        let totp = Totp::parse("otpauth://totp/Acme:alice?secret=jbsw%20y3dp&algorithm=SHA256&digits=8&period=60").unwrap();
+        assert_eq!((totp.secret.as_str(), totp.digits, totp.period), ("JBSWY3DP", 8, 60));
+        assert!(totp.algorithm == TotpAlgorithm::Sha256);
+
+        let totp = Totp::parse("jbsw y3dp ehpk 3pxp").unwrap();
+        assert_eq!((totp.secret.as_str(), totp.digits, totp.period), ("JBSWY3DPEHPK3PXP", 6, 30));
+        assert!(Totp::parse(&totp.to_uri("Acme Inc", "alice@example.com")).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        assert!(matches!(Totp::parse("otpauth://hotp/x?secret=JBSWY3DP"), Err(Error::Parse(_))));
+        assert!(matches!(Totp::parse("otpauth://totp/x?secret=JBSWY3DP&period=0"), Err(Error::Parse(_))));
+        assert!(matches!(Totp::parse("otpauth://totp/x?secret=JBSWY3DP&digits=9"), Err(Error::Parse(_))));
+        assert!(matches!(Totp::parse("not base32!"), Err(Error::Parse(_))));
+
+        let zero_period = Totp { period: 0, ..rfc_totp(TotpAlgorithm::Sha1) };
+        assert!(matches!(zero_period.code_at(59), Err(Error::Parse(_))));
+        let json = r#"{"secret":"JBSWY3DP","digits":6,"period":0,"algorithm":"Sha1"}"#;
+        assert!(matches!(serde_json::from_str::<Totp>(json).unwrap().validate(), Err(Error::Parse(_))));
+    }
+}
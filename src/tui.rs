@@ -0,0 +1,644 @@
+use crate::clipboard;
+use crate::session::{Session, SECRET_MASK};
+use dplock::agent;
+use dplock::generator::{self, Policy};
+use dplock::vault::fuzzy_score;
+use dplock::{Entry, MasterKey, Vault, VaultData};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::env;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const BROWSE_HELP: &str = "↑↓ move  / search  r reveal  u/p/o copy user/password/OTP  a add  e edit  d delete  l lock  q quit";
+const FORM_HELP: &str = "Tab/↑↓ switch field  Enter next/save  Esc cancel";
+
+/// Seconds without a key press before the session locks, from `DPLOCK_TUI_IDLE_TIMEOUT`.
+fn idle_timeout() -> Duration {
+    let secs = env::var("DPLOCK_TUI_IDLE_TIMEOUT")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(300); // Default to 5 minutes
+    Duration::from_secs(secs)
+}
+
+/// Raw mode on the alternate screen, restored on drop even when the TUI fails.
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Identifies an entry across reloads of the vault, which may reorder or change it.
+#[derive(Clone)]
+struct EntryKey {
+    name: String,
+    username: String,
+    created_at: i64,
+}
+
+impl EntryKey {
+    fn of(name: &str, entry: &Entry) -> Self {
+        EntryKey { name: name.to_string(), username: entry.username.clone(), created_at: entry.created_at }
+    }
+
+    /// Index of the entry under its name, as the vault is now.
+    fn find(&self, data: &VaultData) -> Result<usize> {
+        data.get(&self.name).iter()
+            .position(|e| e.username == self.username && e.created_at == self.created_at)
+            .ok_or_else(|| anyhow!("❌ '{}' was changed by another dplock process. Reloaded.", self.name))
+    }
+}
+
+struct Input {
+    label: &'static str,
+    value: String,
+    masked: bool,
+}
+
+impl Input {
+    fn new(label: &'static str, value: &str, masked: bool) -> Self {
+        Input { label, value: value.to_string(), masked }
+    }
+
+    fn shown(&self) -> String {
+        if self.masked { "•".repeat(self.value.chars().count()) } else { self.value.clone() }
+    }
+}
+
+struct Form {
+    title: &'static str,
+    inputs: Vec<Input>,
+    focus: usize,
+    /// The entry being edited; `None` when adding.
+    editing: Option<EntryKey>,
+}
+
+impl Form {
+    fn value(&self, label: &str) -> &str {
+        self.inputs.iter().find(|i| i.label == label).map(|i| i.value.trim()).unwrap_or_default()
+    }
+}
+
+enum Mode {
+    Browse,
+    Search,
+    Form(Form),
+    ConfirmDelete(EntryKey),
+    Locked(Input),
+}
+
+struct App<'a> {
+    vault: &'a Vault,
+    master: Option<MasterKey>,
+    data: VaultData,
+    query: String,
+    /// Visible entries as (name, index), in display order.
+    rows: Vec<(String, usize)>,
+    selected: usize,
+    offset: usize,
+    revealed: bool,
+    mode: Mode,
+    status: String,
+    last_activity: Instant,
+}
+
+/// Runs the full-screen browser until the user quits. The master password is asked once;
+/// after `DPLOCK_TUI_IDLE_TIMEOUT` seconds without input the session locks itself.
+pub fn run(session: &Session) -> Result<()> {
+    let master = session.unlock()?;
+    let vault = session.vault();
+    let data = vault.read(&master)?;
+
+    let mut app = App {
+        vault,
+        master: Some(master),
+        data,
+        query: String::new(),
+        rows: Vec::new(),
+        selected: 0,
+        offset: 0,
+        revealed: false,
+        mode: Mode::Browse,
+        status: String::new(),
+        last_activity: Instant::now(),
+    };
+    app.refresh();
+
+    let _screen = Screen::enter()?;
+    let mut out = io::stdout();
+    loop {
+        app.draw(&mut out)?;
+        // Poll with a timeout so the OTP countdown stays current and idle locking kicks in.
+        if event::poll(Duration::from_millis(500))? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    app.last_activity = Instant::now();
+                    if !app.handle_key(key) {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        } else if app.master.is_some() && app.last_activity.elapsed() >= idle_timeout() {
+            app.lock();
+        }
+    }
+    Ok(())
+}
+
+impl App<'_> {
+    fn refresh(&mut self) {
+        let mut rows: Vec<(i64, String, usize)> = self.data.entries.iter()
+            .flat_map(|(name, list)| list.iter().enumerate().map(move |(i, entry)| (name, i, entry)))
+            .filter_map(|(name, i, entry)| {
+                let score = if self.query.is_empty() { Some(0) } else { fuzzy_score(&self.query, name, entry) };
+                score.map(|score| (score, name.clone(), i))
+            })
+            .collect();
+        rows.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)).then_with(|| a.2.cmp(&b.2)));
+        self.rows = rows.into_iter().map(|(_, name, i)| (name, i)).collect();
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+
+    fn select(&mut self, key: &EntryKey) {
+        if let Some(pos) = self.rows.iter().position(|(name, i)| {
+            name == &key.name && self.data.entries[name].get(*i).is_some_and(|e| e.created_at == key.created_at)
+        }) {
+            self.selected = pos;
+        }
+    }
+
+    fn current(&self) -> Option<(&str, usize, &Entry)> {
+        let (name, i) = self.rows.get(self.selected)?;
+        let entry = self.data.entries.get(name)?.get(*i)?;
+        Some((name, *i, entry))
+    }
+
+    fn lock(&mut self) {
+        self.master = None;
+        agent::forget(self.vault.path()); // the unlock prompt may have handed the key over
+        self.data = VaultData::default();
+        self.rows.clear();
+        self.revealed = false;
+        self.query.clear();
+        self.mode = Mode::Locked(Input::new("Master password", "", true));
+        self.status = "🔒 Locked after inactivity.".to_string();
+    }
+
+    /// Handles one key press; returns `false` to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+        self.status.clear();
+        let result = match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => return self.browse_key(key),
+            Mode::Search => {
+                self.search_key(key);
+                Ok(())
+            }
+            Mode::Form(form) => self.form_key(form, key),
+            Mode::ConfirmDelete(entry) => self.confirm_delete_key(entry, key),
+            Mode::Locked(input) => return self.locked_key(input, key),
+        };
+        if let Err(e) = result {
+            self.status = e.to_string();
+            self.reload();
+        }
+        true
+    }
+
+    fn reload(&mut self) {
+        if let Some(master) = &self.master {
+            if let Ok(data) = self.vault.read(master) {
+                self.data = data;
+                self.refresh();
+            }
+        }
+    }
+
+    fn browse_key(&mut self, key: KeyEvent) -> bool {
+        let page = terminal::size().map(|(_, h)| h.saturating_sub(3) as usize).unwrap_or(10).max(1);
+        let last = self.rows.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Esc if self.query.is_empty() => return false,
+            KeyCode::Esc => {
+                self.query.clear();
+                self.refresh();
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(page),
+            KeyCode::PageDown => self.selected = (self.selected + page).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Char('r') => self.revealed = !self.revealed,
+            KeyCode::Char('u') => self.copy(|_, entry| Ok(("Username", entry.username.clone()))),
+            KeyCode::Char('p') => self.copy(|_, entry| Ok((entry.kind.secret_label(), entry.password.clone()))),
+            KeyCode::Char('o') => self.copy(|name, entry| match &entry.totp {
+                Some(totp) => Ok(("OTP code", totp.code_at(Utc::now().timestamp() as u64)?.0)),
+                None => Err(anyhow!("❌ No TOTP secret stored under '{}'.", name)),
+            }),
+            KeyCode::Char('a') => self.mode = Mode::Form(Self::add_form()),
+            KeyCode::Char('e') => {
+                if let Some((name, _, entry)) = self.current() {
+                    self.mode = Mode::Form(Self::edit_form(name, entry));
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Some((name, _, entry)) = self.current() {
+                    self.mode = Mode::ConfirmDelete(EntryKey::of(name, entry));
+                }
+            }
+            KeyCode::Char('l') => {
+                self.lock();
+                self.status = "🔒 Locked.".to_string();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn copy(&mut self, value: impl Fn(&str, &Entry) -> Result<(&'static str, String)>) {
+        let Some((name, _, entry)) = self.current() else {
+            return;
+        };
+        self.status = match value(name, entry).and_then(|(what, text)| Ok((what, clipboard::copy(&text, false)?))) {
+            Ok((what, Some(secs))) => format!("📋 {} copied to clipboard! Clears in {}s.", what, secs),
+            Ok((what, None)) => format!("📋 {} copied to clipboard!", what),
+            Err(e) => e.to_string(),
+        };
+    }
+
+    fn search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.query.clear();
+                self.refresh();
+                return;
+            }
+            KeyCode::Enter => return,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1)),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+                self.refresh();
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.selected = 0;
+                self.refresh();
+            }
+            _ => {}
+        }
+        self.mode = Mode::Search;
+    }
+
+    fn add_form() -> Form {
+        Form {
+            title: "New entry",
+            inputs: vec![
+                Input::new("Name", "", false),
+                Input::new("Username", "", false),
+                Input::new("Password", "", true),
+                Input::new("Message", "", false),
+                Input::new("Tags", "", false),
+            ],
+            focus: 0,
+            editing: None,
+        }
+    }
+
+    fn edit_form(name: &str, entry: &Entry) -> Form {
+        Form {
+            title: "Edit entry",
+            inputs: vec![
+                Input::new("Username", &entry.username, false),
+                Input::new("Password", &entry.password, true),
+                Input::new("Message", entry.message.as_deref().unwrap_or_default(), false),
+                Input::new("Tags", &entry.tags.join(", "), false),
+            ],
+            focus: 0,
+            editing: Some(EntryKey::of(name, entry)),
+        }
+    }
+
+    fn form_key(&mut self, mut form: Form, key: KeyEvent) -> Result<()> {
+        let last = form.inputs.len() - 1;
+        match key.code {
+            KeyCode::Esc => return Ok(()),
+            KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % form.inputs.len(),
+            KeyCode::BackTab | KeyCode::Up => form.focus = (form.focus + last) % form.inputs.len(),
+            KeyCode::Enter if form.focus == last => return self.submit(form),
+            KeyCode::Enter => form.focus += 1,
+            KeyCode::Backspace => {
+                form.inputs[form.focus].value.pop();
+            }
+            KeyCode::Char(c) => form.inputs[form.focus].value.push(c),
+            _ => {}
+        }
+        self.mode = Mode::Form(form);
+        Ok(())
+    }
+
+    fn submit(&mut self, form: Form) -> Result<()> {
+        let master = self.master.clone().ok_or_else(|| anyhow!("🔒 Locked."))?;
+        let username = form.value("Username").to_string();
+        if username.is_empty() {
+            self.status = "❌ Username is required.".to_string();
+            self.mode = Mode::Form(form);
+            return Ok(());
+        }
+        let message = Some(form.value("Message").to_string()).filter(|m| !m.is_empty());
+        let tags: Vec<String> = form.value("Tags").split(',').map(str::to_string).collect();
+        // The password is taken untrimmed; spaces may be part of it.
+        let password = form.inputs.iter().find(|i| i.label == "Password").map(|i| i.value.clone()).unwrap_or_default();
+
+        let key = match &form.editing {
+            Some(key) => {
+                let key = EntryKey { username: username.clone(), ..key.clone() };
+                let original = form.editing.clone().unwrap();
+                self.data = self.vault.update(&master, |data| -> Result<()> {
+                    let index = original.find(data)?;
+                    let entry = data.entry_mut(&original.name, index)?;
+                    entry.username = username;
+                    entry.set_password(password);
+                    entry.message = message;
+                    entry.tags.clear();
+                    entry.add_tags(&tags);
+                    Ok(())
+                })?;
+                self.status = format!("✅ Entry updated: {}", key.name);
+                key
+            }
+            None => {
+                let name = match self.data.name_for_add(form.value("Name")) {
+                    Ok(name) => name,
+                    Err(e) => {
+                        self.status = e.to_string();
+                        self.mode = Mode::Form(form);
+                        return Ok(());
+                    }
+                };
+                let (password, generated) = if password.is_empty() {
+                    (generator::generate(&Policy::parse("")?)?.value, true)
+                } else {
+                    (password, false)
+                };
+                let mut entry = Entry::new(username, password);
+                entry.message = message;
+                entry.add_tags(&tags);
+                let key = EntryKey::of(&name, &entry);
+                self.data = self.vault.update(&master, |data| data.add(&name, entry).map(|_| ()))?;
+                self.status = if generated {
+                    format!("✅ Entry added under: {} (password generated)", name)
+                } else {
+                    format!("✅ Entry added under: {}", name)
+                };
+                key
+            }
+        };
+        self.query.clear();
+        self.refresh();
+        self.select(&key);
+        Ok(())
+    }
+
+    fn confirm_delete_key(&mut self, key: EntryKey, event: KeyEvent) -> Result<()> {
+        if !matches!(event.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+            self.status = "❌ Cancelled.".to_string();
+            return Ok(());
+        }
+        let master = self.master.clone().ok_or_else(|| anyhow!("🔒 Locked."))?;
+        self.data = self.vault.update(&master, |data| -> Result<()> {
+            let index = key.find(data)?;
+            data.remove(&key.name, Some(index))?;
+            Ok(())
+        })?;
+        self.refresh();
+        self.status = format!("🗑️ Removed entry: {} (👤 {})", key.name, key.username);
+        Ok(())
+    }
+
+    fn locked_key(&mut self, mut input: Input, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Backspace => {
+                input.value.pop();
+            }
+            KeyCode::Char(c) => input.value.push(c),
+            KeyCode::Enter => {
+                if let Ok(master) = self.vault.unlock(&input.value) {
+                    match self.vault.read(&master) {
+                        Ok(data) => {
+                            self.data = data;
+                            self.master = Some(master);
+                            self.refresh();
+                            self.status = "🔓 Unlocked.".to_string();
+                            return true;
+                        }
+                        Err(e) => self.status = e.to_string(),
+                    }
+                } else {
+                    thread::sleep(Duration::from_secs(1)); // slow down guessing
+                    self.status = "❌ Incorrect password. Please try again.".to_string();
+                }
+                input.value.clear();
+            }
+            _ => {}
+        }
+        self.mode = Mode::Locked(input);
+        true
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        queue!(out, Clear(ClearType::All))?;
+
+        if let Mode::Locked(input) = &self.mode {
+            let lines = ["🔒 dplock is locked".to_string(), String::new(), format!("{}: {}", input.label, input.shown())];
+            let top = height.saturating_sub(lines.len()) / 2;
+            for (i, line) in lines.iter().enumerate() {
+                let left = width.saturating_sub(line.chars().count()) / 2;
+                queue!(out, MoveTo(left as u16, (top + i) as u16), Print(line))?;
+            }
+            self.draw_footer(out, width, height, "Enter unlock  Esc quit")?;
+            out.flush()?;
+            return Ok(());
+        }
+
+        // Header
+        let search = match (&self.mode, self.query.is_empty()) {
+            (Mode::Search, _) => format!("   🔎 /{}▏", self.query),
+            (_, false) => format!("   🔎 {}", self.query),
+            _ => String::new(),
+        };
+        let header = format!(" 🔐 dplock — {} entr{}{}", self.rows.len(), if self.rows.len() == 1 { "y" } else { "ies" }, search);
+        queue!(out, MoveTo(0, 0), SetAttribute(Attribute::Reverse), Print(pad(&header, width)), SetAttribute(Attribute::Reset))?;
+
+        // Entry list
+        let body = height.saturating_sub(2);
+        let list_width = (width / 3).clamp(20, 40).min(width);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + body {
+            self.offset = self.selected + 1 - body;
+        }
+        for row in 0..body {
+            queue!(out, MoveTo(0, (row + 1) as u16))?;
+            if let Some((name, i)) = self.rows.get(self.offset + row) {
+                let entry = &self.data.entries[name][*i];
+                let text = pad(&format!(" {}  {}", name, entry.username), list_width.saturating_sub(1));
+                if self.offset + row == self.selected {
+                    queue!(out, SetAttribute(Attribute::Reverse), Print(text), SetAttribute(Attribute::Reset))?;
+                } else {
+                    queue!(out, Print(text))?;
+                }
+            }
+            queue!(out, MoveTo(list_width as u16, (row + 1) as u16), Print("│"))?;
+        }
+        if self.rows.is_empty() {
+            let text = if self.query.is_empty() { " No entries yet (a to add)" } else { " No matches" };
+            queue!(out, MoveTo(0, 1), Print(pad(text, list_width.saturating_sub(1))))?;
+        }
+
+        // Detail pane, or the form being filled in
+        let pane_width = width.saturating_sub(list_width + 2);
+        let lines = match &self.mode {
+            Mode::Form(form) => Self::form_lines(form),
+            _ => self.detail_lines(),
+        };
+        for (row, line) in lines.iter().take(body).enumerate() {
+            queue!(out, MoveTo((list_width + 2) as u16, (row + 1) as u16), Print(fit(line, pane_width)))?;
+        }
+
+        let help = match &self.mode {
+            Mode::Form(_) => FORM_HELP.to_string(),
+            Mode::Search => "Type to filter  Enter keep  Esc clear".to_string(),
+            Mode::ConfirmDelete(key) => format!("Delete '{}' (👤 {})? y/n", key.name, key.username),
+            _ => BROWSE_HELP.to_string(),
+        };
+        self.draw_footer(out, width, height, &help)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn draw_footer(&self, out: &mut impl Write, width: usize, height: usize, help: &str) -> Result<()> {
+        let text = if self.status.is_empty() { help } else { &self.status };
+        queue!(out, MoveTo(0, height.saturating_sub(1) as u16), SetAttribute(Attribute::Reverse), Print(pad(&format!(" {}", text), width)), SetAttribute(Attribute::Reset))?;
+        Ok(())
+    }
+
+    fn detail_lines(&self) -> Vec<String> {
+        let Some((name, index, entry)) = self.current() else {
+            return Vec::new();
+        };
+        let mask = |value: &str, secret: bool| {
+            if secret && !self.revealed { SECRET_MASK.to_string() } else { first_line(value) }
+        };
+        let date = |t: i64| chrono::DateTime::from_timestamp(t, 0)
+            .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let now = Utc::now().timestamp();
+
+        let mut lines = vec![
+            format!("Name:      {}  (#{})", name, index + 1),
+            format!("Username:  {}", entry.username),
+        ];
+        if entry.kind != Default::default() {
+            lines.push(format!("Type:      {}", entry.kind.as_str()));
+        }
+        lines.push(format!("{:<10} {}", format!("{}:", entry.kind.secret_label()), mask(&entry.password, true)));
+        for field in &entry.fields {
+            lines.push(format!("{:<10} {}", format!("{}:", field.name), mask(&field.value, field.secret)));
+        }
+        if let Some(totp) = &entry.totp {
+            let code = match totp.code_at(now as u64) {
+                Ok((code, remaining)) if self.revealed => format!("{} ({}s)", code, remaining),
+                Ok(_) => format!("{} (o to copy)", SECRET_MASK),
+                Err(e) => e.to_string(),
+            };
+            lines.push(format!("TOTP:      {}", code));
+        }
+        if !entry.tags.is_empty() {
+            lines.push(format!("Tags:      {}", entry.tags.join(", ")));
+        }
+        if let Some(message) = &entry.message {
+            lines.push(format!("Message:   {}", first_line(message)));
+        }
+        if let Some(expired) = entry.expired {
+            let state = if expired <= now { "expired" } else { "expires" };
+            lines.push(format!("Expiry:    {} ({})", date(expired), state));
+        }
+        if let Some(remind) = entry.remind {
+            lines.push(format!("Remind:    {}", date(remind)));
+        }
+        lines.push(format!("Created:   {}", date(entry.created_at)));
+        if !entry.history.is_empty() {
+            lines.push(format!("History:   {} previous password(s)", entry.history.len()));
+        }
+        if !self.revealed {
+            lines.push(String::new());
+            lines.push("Press r to reveal secrets.".to_string());
+        }
+        lines
+    }
+
+    fn form_lines(form: &Form) -> Vec<String> {
+        let mut lines = vec![form.title.to_string(), String::new()];
+        for (i, input) in form.inputs.iter().enumerate() {
+            let cursor = if i == form.focus { "▏" } else { "" };
+            let marker = if i == form.focus { ">" } else { " " };
+            lines.push(format!("{} {:<9} {}{}", marker, format!("{}:", input.label), input.shown(), cursor));
+        }
+        if form.editing.is_none() {
+            lines.push(String::new());
+            lines.push("Leave the password empty to generate one. Tags are comma-separated.".to_string());
+        }
+        lines
+    }
+}
+
+fn first_line(value: &str) -> String {
+    let mut lines = value.lines();
+    let first = lines.next().unwrap_or_default().to_string();
+    if lines.next().is_some() { format!("{} …", first) } else { first }
+}
+
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+        cut.push('…');
+        cut
+    }
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let text = fit(text, width);
+    let len = text.chars().count();
+    format!("{}{}", text, " ".repeat(width.saturating_sub(len)))
+}
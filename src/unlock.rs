@@ -0,0 +1,77 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+
+pub const PASSWORD_ENV: &str = "DPLOCK_PASSWORD";
+
+/// A non-interactive source for the master password, for scripts and CI.
+pub enum PasswordSource {
+    Fd(u32),
+    File(PathBuf),
+    Env,
+}
+
+impl PasswordSource {
+    /// `--password-fd` or `--password-file` when given, else `DPLOCK_PASSWORD` when it is set.
+    pub fn from_args(fd: Option<u32>, file: Option<&str>) -> Option<Self> {
+        match (fd, file) {
+            (Some(fd), _) => Some(PasswordSource::Fd(fd)),
+            (None, Some(path)) => Some(PasswordSource::File(PathBuf::from(path))),
+            (None, None) if env::var_os(PASSWORD_ENV).is_some() => Some(PasswordSource::Env),
+            (None, None) => None,
+        }
+    }
+
+    /// Reads the password: the first line, without its line ending.
+    pub fn read(&self) -> Result<String> {
+        let password = match self {
+            // Through /dev/fd, so the descriptor is neither taken over nor closed.
+            PasswordSource::Fd(0) => read_stdin_line(),
+            PasswordSource::Fd(fd) => File::open(format!("/dev/fd/{}", fd)).map_err(anyhow::Error::from).and_then(|f| first_line(BufReader::new(f))),
+            PasswordSource::File(path) => File::open(path).map_err(anyhow::Error::from).and_then(|f| first_line(BufReader::new(f))),
+            PasswordSource::Env => {
+                eprintln!("⚠️  Reading the master password from {}. Other processes of this user may be able to see it; prefer --password-fd or --password-file.", PASSWORD_ENV);
+                env::var(PASSWORD_ENV).map_err(|e| anyhow!("{e}"))
+            }
+        }
+        .map_err(|e| anyhow!("❌ Could not read the master password from {}: {e}", self))?;
+
+        if password.is_empty() {
+            return Err(anyhow!("❌ The master password from {} is empty.", self));
+        }
+        Ok(password)
+    }
+}
+
+impl fmt::Display for PasswordSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PasswordSource::Fd(fd) => write!(f, "file descriptor {}", fd),
+            PasswordSource::File(path) => write!(f, "'{}'", path.display()),
+            PasswordSource::Env => write!(f, "{}", PASSWORD_ENV),
+        }
+    }
+}
+
+/// Whether a password prompt can be shown; prompts read from the terminal, not stdin.
+pub fn has_terminal() -> bool {
+    File::open("/dev/tty").is_ok()
+}
+
+/// First line of stdin without its line ending, e.g. for `add --password-stdin`.
+pub fn read_stdin_line() -> Result<String> {
+    first_line(io::stdin().lock())
+}
+
+fn first_line(mut reader: impl BufRead) -> Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(anyhow!("no input"));
+    }
+    let len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+    Ok(line)
+}
@@ -1,12 +1,64 @@
-use std::io;
+use std::fs::{self, File, OpenOptions};
 use std::io::Write;
-use anyhow::anyhow;
+use std::path::Path;
+use crate::Error;
 use base64::Engine;
 use chrono::Utc;
 use serde_json::Value;
 use terminal_size::terminal_size;
 use base64::engine::general_purpose::STANDARD as base64_engine;
 
+/// Writes `contents` to a temp file next to `path`, fsyncs it and renames it into place,
+/// so a crash never leaves a half-written file behind.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> crate::Result<()> {
+    write_atomic_with_mode(path, contents, None)
+}
+
+/// Like `write_atomic`, but the file is only readable by the current user (mode 0600 on Unix).
+pub fn write_secret_file(path: &Path, contents: &[u8]) -> crate::Result<()> {
+    write_atomic_with_mode(path, contents, Some(0o600))
+}
+
+fn write_atomic_with_mode(path: &Path, contents: &[u8], mode: Option<u32>) -> crate::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir)?;
+    let file_name = path.file_name()
+        .ok_or_else(|| Error::Other(format!("Invalid file path: {}", path.display())))?;
+    let tmp_path = dir.join(format!(".{}.tmp-{}", file_name.to_string_lossy(), std::process::id()));
+
+    let result = (|| -> crate::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    // Persist the rename itself; not every platform allows syncing a directory.
+    if let Ok(dir_handle) = File::open(dir) {
+        let _ = dir_handle.sync_all();
+    }
+    Ok(())
+}
+
 pub fn get_terminal_width() -> usize {
     if let Some((w, _)) = terminal_size() {
         w.0 as usize
@@ -47,14 +99,14 @@ pub fn is_encrypted(data: &[u8]) -> bool {
 
 
 
-pub fn parse_expired_time(input: &str) -> anyhow::Result<i64> {
-    let now = Utc::now().timestamp();
+/// Sums a duration like `3h`, `1d`, `2w1d`, `3m` or `4y` into seconds. Returns 0 if nothing matched.
+pub fn parse_duration_secs(input: &str) -> crate::Result<i64> {
     let trimmed = input.trim().to_lowercase();
-    let re = regex::Regex::new(r"(\d+)([hdwmy])")?;
+    let re = regex::Regex::new(r"(\d+)([hdwmy])").map_err(Error::other)?;
     let mut total_secs = 0;
 
     for cap in re.captures_iter(&trimmed) {
-        let num: i64 = cap[1].parse()?;
+        let num: i64 = cap[1].parse().map_err(|_| Error::parse(format!("Duration too large: {}", &cap[0])))?;
         let unit = &cap[2];
         total_secs += match unit {
             "h" => num * 3600,
@@ -66,36 +118,28 @@ pub fn parse_expired_time(input: &str) -> anyhow::Result<i64> {
         };
     }
 
+    Ok(total_secs)
+}
+
+pub fn parse_expired_time(input: &str) -> crate::Result<i64> {
+    let now = Utc::now().timestamp();
+    let total_secs = parse_duration_secs(input)?;
+
     if total_secs == 0 {
-        Err(anyhow!("❌ Invalid expired time format. Use e.g: 3h, 1d, 2w, 3m, 4y"))
+        Err(Error::parse("Invalid expired time format. Use e.g: 3h, 1d, 2w, 3m, 4y"))
     } else {
         Ok(now + total_secs)
     }
 }
 
-pub fn parse_remind_time(expired: Option<i64>, input: &str) -> anyhow::Result<i64> {
+pub fn parse_remind_time(expired: Option<i64>, input: &str) -> crate::Result<i64> {
     let expired_secs = expired.unwrap_or(0);
-    let trimmed = input.trim().to_lowercase();
-    let re = regex::Regex::new(r"(\d+)([hdwmy])")?;
-    let mut total_secs = 0;
-
-    for cap in re.captures_iter(&trimmed) {
-        let num: i64 = cap[1].parse()?;
-        let unit = &cap[2];
-        total_secs += match unit {
-            "h" => num * 3600,
-            "d" => num * 86400,
-            "w" => num * 7 * 86400,
-            "m" => num * 30 * 86400,
-            "y" => num * 365 * 86400,
-            _ => 0,
-        };
-    }
+    let total_secs = parse_duration_secs(input)?;
 
     if total_secs == 0 {
-        Err(anyhow!("❌ Invalid remind time format. Use e.g: 3h, 1d, 2w, 3m, 4y"))
+        Err(Error::parse("Invalid remind time format. Use e.g: 3h, 1d, 2w, 3m, 4y"))
     } else if expired_secs == 0 {
-        Err(anyhow!("❌ Invalid expired time format. Use e.g: 3h, 1d, 2w, 3m, 4y"))
+        Err(Error::parse("Invalid expired time format. Use e.g: 3h, 1d, 2w, 3m, 4y"))
     }
     else {
         Ok(expired_secs - total_secs)
@@ -105,13 +149,3 @@ pub fn parse_remind_time(expired: Option<i64>, input: &str) -> anyhow::Result<i6
 pub fn compute_wait_time(attempts: u32) -> u64 {
         2u64.pow((attempts - 5).min(5))
     }
-    
-pub fn wait_with_countdown(wait_minutes: u64) -> anyhow::Result<()> {
-        for remaining in (1..=wait_minutes * 60).rev() {
-            print!("\r⏳ Please wait {} second(s)...", remaining);
-            io::stdout().flush()?;
-            std::thread::sleep(std::time::Duration::from_secs(1));
-        }
-        println!();
-        Ok(())
-    }
\ No newline at end of file
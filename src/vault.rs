@@ -1,17 +1,22 @@
-use crate::crypto;
+use crate::agent;
+use crate::crypto::{self, VaultKey};
+use crate::error::{Error, Result};
+use crate::folder;
+use crate::fuzzy;
+use crate::keyfile::Keyfile;
+use crate::lock::VaultLock;
+use crate::recovery::RecoveryKey;
+use crate::template::EntryKind;
+use crate::totp::Totp;
+use crate::utils::write_secret_file;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::PathBuf};
-use anyhow::{anyhow, Result};
-use arboard::Clipboard;
-use rpassword::prompt_password;
-use std::io::{self, Write};
-use chrono::{Utc};
-use crossterm::event::{Event, KeyCode};
-use crossterm::{event};
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
-use textwrap::wrap;
-use crate::utils::{compute_wait_time, get_terminal_width, is_encrypted, parse_expired_time, parse_remind_time, wait_with_countdown};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Entry {
@@ -23,550 +28,660 @@ pub struct Entry {
     pub created_at: i64,
     #[serde(default)]
     pub message: Option<String>,
+    #[serde(default)]
+    pub totp: Option<Totp>,
+    #[serde(default)]
+    pub kind: EntryKind,
+    #[serde(default)]
+    pub fields: Vec<CustomField>,
+    /// Previous passwords, most recent first.
+    #[serde(default)]
+    pub history: Vec<PreviousPassword>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
-fn default_timestamp() -> i64 {
-    Utc::now().timestamp()
-}
-#[derive(Serialize, Deserialize, Default, Clone)]
-pub struct VaultData {
-    pub entries: HashMap<String, Vec<Entry>>,
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PreviousPassword {
+    pub password: String,
+    pub replaced_at: i64,
 }
 
-pub struct Vault {
-    vault_file: PathBuf,
+/// Number of previous passwords kept per entry.
+const HISTORY_LIMIT: usize = 10;
+
+/// A named extra value on an entry, kept in the order it was added. Secret fields are
+/// masked in listings and encrypted in exports like the password.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CustomField {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub secret: bool,
 }
 
-impl Vault {
-    pub fn new(vault_file: Option<PathBuf>) -> Self {
-        let vault_file = vault_file.unwrap_or_else(|| {
-            dirs::home_dir().unwrap().join(".dplock/vault.bin")
-        });
-        Self {
-            vault_file
+impl Entry {
+    /// A new login entry created now, without expiry, tags or fields.
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Entry {
+            username: username.into(),
+            password: password.into(),
+            expired: None,
+            remind: None,
+            created_at: Utc::now().timestamp(),
+            message: None,
+            totp: None,
+            kind: EntryKind::default(),
+            fields: Vec::new(),
+            history: Vec::new(),
+            tags: Vec::new(),
         }
     }
 
-    fn vault_path(&self) -> &PathBuf {
-        &self.vault_file
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
     }
 
-    fn save_master_to_keyring(&self, password: &str) -> Result<()> {
-        let service = "dplock";
-        let username = self.vault_path().to_string_lossy();
-        let entry = keyring::Entry::new(service, &username)?;
-        entry.set_password(password)?;
-        Ok(())
-    }
-    
-    fn load_master_from_keyring(&self) -> Result<Option<String>> {
-        let service = "dplock";
-        let username = self.vault_path().to_string_lossy();
-        let entry = keyring::Entry::new(service, &username)?;
-        match entry.get_password() {
-            Ok(password) => Ok(Some(password)),
-            Err(keyring::Error::NoEntry) => Ok(None),
-            Err(e) => Err(anyhow!("Keyring error: {e}")),
+    /// Adds tags the entry doesn't have yet, ignoring case and a leading '#'.
+    pub fn add_tags(&mut self, tags: &[String]) {
+        for tag in tags.iter().map(|t| normalize_tag(t)) {
+            if !tag.is_empty() && !self.has_tag(&tag) {
+                self.tags.push(tag);
+            }
         }
     }
-    
-    fn clear_master_from_keyring(&self) -> Result<()> {
-        let service = "dplock";
-        let username = self.vault_path().to_string_lossy();
-        let entry = keyring::Entry::new(service, &username)?;
-        let _ = entry.delete_password(); // ignore if not found
-        Ok(())
-    }
-    
-    fn save_attempts_to_keyring(&self, attempts: u32) -> Result<()> {
-        let service = "dplock_attempts";
-        let username = self.vault_path().to_string_lossy();
-        let entry = keyring::Entry::new(service, &username)?;
-        entry.set_password(&attempts.to_string())?;
-        Ok(())
-    }
 
-    fn load_attempts_from_keyring(&self) -> Result<u32> {
-        let service = "dplock_attempts";
-        let username = self.vault_path().to_string_lossy();
-        let entry = keyring::Entry::new(service, &username)?;
-        match entry.get_password() {
-            Ok(attempts) => Ok(attempts.parse().unwrap_or(0)),
-            Err(keyring::Error::NoEntry) => Ok(0),
-            Err(e) => Err(anyhow!("Keyring error: {e}")),
-        }
+    pub fn field(&self, name: &str) -> Option<&CustomField> {
+        self.fields.iter().find(|f| f.name.eq_ignore_ascii_case(name))
     }
 
-    fn clear_attempts_from_keyring(&self) -> Result<()> {
-        let service = "dplock_attempts";
-        let username = self.vault_path().to_string_lossy();
-        let entry = keyring::Entry::new(service, &username)?;
-        let _ = entry.delete_password(); // Ignore if not found
-        Ok(())
+    /// Replaces the password, keeping the old one in the history.
+    pub fn set_password(&mut self, password: String) {
+        if password == self.password {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.password, password);
+        self.history.insert(0, PreviousPassword { password: previous, replaced_at: Utc::now().timestamp() });
+        self.history.truncate(HISTORY_LIMIT);
     }
 
-    fn prompt_password(prompt: &str) -> Result<String> {
-        prompt_password(prompt).map_err(|e| anyhow!("Failed to read password: {e}"))
-    }
-    fn get_master_password(&self, prompt: &str) -> Result<String> {
-        let cache_duration: i64 = env::var("DPLOCK_CACHE_DURATION")
-            .ok()
-            .and_then(|val| val.parse().ok())
-            .unwrap_or(600); // Default to 600 seconds
-    
-        if let Some(password) = self.load_master_from_keyring()? {
-            let now = Utc::now().timestamp();
-            let parts: Vec<&str> = password.splitn(2, ':').collect();
-            if parts.len() == 2 {
-                let cached_time: i64 = parts[0].parse().unwrap_or(0);
-                if now - cached_time <= cache_duration {
-                    return Ok(parts[1].to_string());
-                }
-            }
+    /// Rewrites every secret the entry holds: the password and its history, the TOTP secret
+    /// and secret fields.
+    pub fn map_secrets<E>(&mut self, f: impl Fn(&str) -> std::result::Result<String, E>) -> std::result::Result<(), E> {
+        self.password = f(&self.password)?;
+        for previous in self.history.iter_mut() {
+            previous.password = f(&previous.password)?;
         }
-    
-        let mut attempts = self.load_attempts_from_keyring()?;
-    
-        if attempts > 5 {
-            let wait_time = compute_wait_time(attempts);
-            println!("⏳ Too many failed attempts. Please wait {} minute(s) before trying again.", wait_time);
-            wait_with_countdown(wait_time)?;
+        if let Some(totp) = self.totp.as_mut() {
+            totp.secret = f(&totp.secret)?;
         }
-    
-        loop {
-            let password = Self::prompt_password(prompt)?;
-            if self.load_vault(&password).is_ok() {
-                let now = Utc::now().timestamp();
-                self.save_master_to_keyring(&format!("{}:{}", now, password))?;
-                self.clear_attempts_from_keyring()?;
-                return Ok(password);
-            } else {
-                attempts += 1;
-                self.save_attempts_to_keyring(attempts)?;
-                println!("❌ Incorrect password. Please try again.");
-    
-                if attempts > 5 {
-                    let wait_time = compute_wait_time(attempts);
-                    println!("⏳ Please wait {} minute(s) before trying again.", wait_time);
-                    wait_with_countdown(wait_time)?;
-                }
-            }
+        for field in self.fields.iter_mut().filter(|field| field.secret) {
+            field.value = f(&field.value)?;
         }
+        Ok(())
     }
-    
+}
 
-    fn prompt_optional_expired_time(prompt: &str) -> Result<Option<i64>> {
-        print!("{}", prompt);
-        io::stdout().flush()?;
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let trimmed = input.trim();
+/// A tag as stored: trimmed and without a leading '#'.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_string()
+}
 
-        if trimmed.is_empty() {
-            Ok(None)
-        } else {
-            parse_expired_time(trimmed).map(Some)
-        }
-    }
+fn default_timestamp() -> i64 {
+    Utc::now().timestamp()
+}
 
-    fn prompt_optional_remind_time( expired: Option<i64>, prompt: &str) -> Result<Option<i64>> {
-        print!("{}", prompt);
-        io::stdout().flush()?;
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let trimmed = input.trim();
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct VaultData {
+    pub entries: HashMap<String, Vec<Entry>>,
+}
 
-        if trimmed.is_empty() {
-            Ok(None)
-        } else {
-            parse_remind_time(expired, trimmed).map(Some)
+/// One result of `VaultData::search`.
+pub struct Match<'a> {
+    pub name: &'a str,
+    /// Position under `name`, zero-based.
+    pub index: usize,
+    pub entry: &'a Entry,
+    pub score: i64,
+}
+
+impl VaultData {
+    /// Entries under `name`, in the order they were added.
+    pub fn get(&self, name: &str) -> &[Entry] {
+        self.entries.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Index of the one entry under `name` whose username contains `username`.
+    pub fn find(&self, name: &str, username: Option<&str>) -> Result<usize> {
+        let not_found = || Error::EntryNotFound { name: name.to_string(), username: username.map(str::to_string) };
+        let entry_list = self.entries.get(name).ok_or_else(not_found)?;
+        let matches: Vec<usize> = entry_list.iter().enumerate()
+            .filter(|(_, entry)| username.is_none_or(|u| entry.username.contains(u)))
+            .map(|(i, _)| i)
+            .collect();
+
+        match matches.as_slice() {
+            [] => Err(not_found()),
+            [idx] => Ok(*idx),
+            _ => Err(Error::AmbiguousEntry {
+                name: name.to_string(),
+                usernames: matches.iter().map(|i| entry_list[*i].username.clone()).collect(),
+            }),
         }
     }
 
-    fn load_vault(&self, password: &str) -> Result<VaultData> {
-        let bytes = fs::read(self.vault_path())?;
-        crypto::decrypt(&bytes, password)
+    /// Entries matching `query`, best first. An existing name (or `exact`) matches only
+    /// itself; otherwise names, usernames and tags are matched fuzzily.
+    pub fn search(&self, query: &str, username: Option<&str>, exact: bool) -> Vec<Match<'_>> {
+        let exact = exact || self.entries.contains_key(query);
+        let mut matches: Vec<Match> = self.entries.iter()
+            .filter(|(name, _)| !exact || name.as_str() == query)
+            .flat_map(|(name, entries)| entries.iter().enumerate().map(move |(index, entry)| (name, index, entry)))
+            .filter(|(_, _, entry)| username.is_none_or(|u| entry.username.contains(u)))
+            .filter_map(|(name, index, entry)| {
+                let score = if exact { Some(0) } else { fuzzy_score(query, name, entry) };
+                score.map(|score| Match { name, index, entry, score })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(b.name)).then_with(|| a.index.cmp(&b.index)));
+        matches
     }
 
-    pub fn init(&self) -> Result<()> {
-        self.clear_master_from_keyring()?;
-        let path = self.vault_path();
-        if path.exists() {
-            println!("⚠️  Vault already exists at: {}", path.display());
-            if !Self::confirm_overwrite(path)? {
-                println!("❌ Initialization cancelled.");
-                return Ok(());
-            }
+    /// The stored name that `name` refers to: `name` itself if it exists, else its normalized
+    /// form (see `folder::normalize_name`) if that exists. Anything else comes back unchanged,
+    /// for fuzzy matching or a not-found error.
+    pub fn resolve_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        if self.entries.contains_key(name) {
+            return Cow::Borrowed(name);
+        }
+        match folder::normalize_name(name) {
+            Ok(normalized) if self.entries.contains_key(&normalized) => Cow::Owned(normalized),
+            _ => Cow::Borrowed(name),
         }
+    }
 
-        let new_password = prompt_password("Set new master password: ")?;
-        Self::save_vault(path, &VaultData::default(), &new_password)?;
-        println!("🔐 Vault initialized!");
-        Ok(())
+    /// The name a new entry under `name` is stored under: an existing name as it is, so entries
+    /// saved before names were normalized stay reachable, else the normalized form.
+    pub fn name_for_add(&self, name: &str) -> Result<String> {
+        if self.entries.contains_key(name) {
+            return Ok(name.to_string());
+        }
+        folder::normalize_name(name)
     }
 
-    pub fn add(&self, name: &str, username: &str, use_time: bool, message: Option<&str>) -> Result<()> {
-        let master = self.get_master_password("🔐 Master password: ")?;
-        let entry_pass = Self::prompt_password(format!("🔑 '{username}' password: ").as_str())?;
-        let mut data = self.load_vault(&master)?;
+    /// Adds `entry` under `name` (see `name_for_add`) and returns its index.
+    pub fn add(&mut self, name: &str, entry: Entry) -> Result<usize> {
+        let name = self.name_for_add(name)?;
+        let entry_list = self.entries.entry(name).or_default();
+        entry_list.push(entry);
+        Ok(entry_list.len() - 1)
+    }
 
-        let mut expired = None;
-        let mut remind = None;
+    /// The entry at `index` under `name`, to change in place.
+    pub fn entry_mut(&mut self, name: &str, index: usize) -> Result<&mut Entry> {
+        self.entries.get_mut(name)
+            .and_then(|entry_list| entry_list.get_mut(index))
+            .ok_or_else(|| Error::EntryNotFound { name: name.to_string(), username: None })
+    }
 
-        if use_time {
-            expired = Self::prompt_optional_expired_time("⌛ Expired (e.g: 8h, 3d, 1w, 2m, 3y) or leave blank: ")?;
-            remind = Self::prompt_optional_remind_time(expired, "🔔 Remind before (e.g: 8h, 3d, 1w, 2m, 3y) or leave blank: ")?;
+    /// Removes the entry at `index` under `name`, or all of them, and returns what was removed.
+    pub fn remove(&mut self, name: &str, index: Option<usize>) -> Result<Vec<Entry>> {
+        let not_found = || Error::EntryNotFound { name: name.to_string(), username: None };
+        let entry_list = self.entries.get_mut(name).ok_or_else(not_found)?;
+        let removed = match index {
+            Some(index) if index < entry_list.len() => vec![entry_list.remove(index)],
+            Some(_) => return Err(not_found()),
+            None => std::mem::take(entry_list),
+        };
+        if entry_list.is_empty() {
+            self.entries.remove(name);
         }
+        Ok(removed)
+    }
 
-        let entry = Entry {
-            username: username.to_string(),
-            password: entry_pass,
-            expired,
-            remind,
-            created_at: Utc::now().timestamp(),
-            message: message.map(|m| m.to_string()), // Store the message
-        };
+    /// Number of entries under all names.
+    pub fn len(&self) -> usize {
+        self.entries.values().map(Vec::len).sum()
+    }
 
-        data.entries.entry(name.to_string()).or_default().push(entry);
-        Self::save_vault(self.vault_path(), &data, &master)?;
-        println!("✅ Entry added under: {}", name);
-        Ok(())
+    pub fn is_empty(&self) -> bool {
+        self.entries.values().all(Vec::is_empty)
     }
+}
 
+/// Best fuzzy score of `query` over the entry's name, username and tags.
+pub fn fuzzy_score(query: &str, name: &str, entry: &Entry) -> Option<i64> {
+    let name_score = fuzzy::score(query, name);
+    // The last path segment counts as much as the full name, so `prod` finds `work/aws/prod`.
+    let leaf_score = name.rsplit('/').next().and_then(|leaf| fuzzy::score(query, leaf));
+    let username_score = fuzzy::score(query, &entry.username).map(|s| s - 10);
+    let tag_score = entry.tags.iter().filter_map(|t| fuzzy::score(query, t)).max().map(|s| s - 10);
+    [name_score, leaf_score, username_score, tag_score].into_iter().flatten().max()
+}
 
-    pub fn get(&self, name: &str, username: Option<&str>, show: bool) -> Result<()> {
-        let password = self.get_master_password("Master password: ")?;
-        let data = self.load_vault(&password)?;
+/// `Entry` as laid out in bincode vaults (format versions 0 and 1). Never add fields here.
+#[derive(Deserialize)]
+struct LegacyEntry {
+    username: String,
+    password: String,
+    expired: Option<i64>,
+    remind: Option<i64>,
+    created_at: i64,
+    message: Option<String>,
+}
 
-        let regex = regex::Regex::new(name).map_err(|e| anyhow!("Invalid regex: {e}"))?;
+#[derive(Deserialize)]
+struct LegacyVaultData {
+    entries: HashMap<String, Vec<LegacyEntry>>,
+}
 
-        let matched = data.entries.iter()
-            .filter(|(key, _)| regex.is_match(key))
-            .collect::<Vec<_>>();
+impl From<LegacyVaultData> for VaultData {
+    fn from(legacy: LegacyVaultData) -> Self {
+        let entries = legacy.entries.into_iter().map(|(name, entry_list)| {
+            let entry_list = entry_list.into_iter().map(|e| Entry {
+                username: e.username,
+                password: e.password,
+                expired: e.expired,
+                remind: e.remind,
+                created_at: e.created_at,
+                message: e.message,
+                totp: None,
+                kind: EntryKind::default(),
+                fields: Vec::new(),
+                history: Vec::new(),
+                tags: Vec::new(),
+            }).collect();
+            (name, entry_list)
+        }).collect();
+        VaultData { entries }
+    }
+}
 
-        if matched.is_empty() {
-            println!("❌ No entries found matching: '{}'", name);
-            return Ok(());
-        }
 
-        for (entry_name, entries) in matched {
-            let filtered_entries: Vec<_> = if let Some(username) = username {
-                entries.iter().filter(|entry| entry.username.contains(username)).collect()
-            } else {
-                entries.iter().collect()
-            };
+/// What opens a vault: a key derived in this process, or the agent's copy of it.
+#[derive(Clone)]
+pub enum MasterKey {
+    Local(VaultKey),
+    Agent,
+}
 
-            if filtered_entries.is_empty() {
-                println!("❌ No entries found matching username '{}' under '{}'", username.unwrap_or(""), entry_name);
-                continue;
-            }
+fn local_key(key: &MasterKey) -> Result<&VaultKey> {
+    match key {
+        MasterKey::Local(key) => Ok(key),
+        MasterKey::Agent => Err(Error::other("This needs the key from the master password, not the agent's copy")),
+    }
+}
 
-            println!("🔐 Found {} entr{} for: {}", filtered_entries.len(), if filtered_entries.len() > 1 { "ies" } else { "y" }, entry_name);
+/// A vault file. Nothing is read until it is unlocked.
+///
+/// `load` and `save` don't lock the file; `read` and `update` do, and `lock` covers longer
+/// read-modify-write cycles.
+pub struct Vault {
+    path: PathBuf,
+    keyfile: Option<PathBuf>,
+    lock_notice: Option<fn()>,
+}
 
-            for (i, entry) in filtered_entries.iter().enumerate() {
-                Self::print_entry_info(entry, i, show)?;
-            }
-        }
-        Ok(())
+impl Vault {
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Vault { path: path.into(), keyfile: None, lock_notice: None }
     }
 
+    /// Uses `keyfile` as a second factor: it's read when unlocking a vault that requires one,
+    /// and a vault saved with `save_with_password` requires it from then on.
+    pub fn with_keyfile(mut self, keyfile: impl Into<PathBuf>) -> Self {
+        self.keyfile = Some(keyfile.into());
+        self
+    }
 
-    pub fn list(&self, filter: Option<&str>, sort: Option<&str>) -> Result<()> {
-        let password = self.get_master_password("Master password: ")?;
-        let data = self.load_vault(&password)?;
+    /// Calls `notice` when a lock has to wait for another process, e.g. to tell the user why
+    /// nothing happens.
+    pub fn on_lock_wait(mut self, notice: fn()) -> Self {
+        self.lock_notice = Some(notice);
+        self
+    }
 
-        let mut entries: Vec<_> = data.entries.iter().flat_map(|(name, entry_list)| {
-            entry_list.iter().map(move |entry| (name, entry))
-        }).collect();
+    pub fn keyfile(&self) -> Option<&Path> {
+        self.keyfile.as_deref()
+    }
 
-        Self::apply_filter_and_sort(&mut entries, filter, sort);
+    /// `~/.dplock/vault.bin`
+    pub fn default_path() -> PathBuf {
+        dirs::home_dir().unwrap_or_default().join(".dplock/vault.bin")
+    }
 
-        if entries.is_empty() {
-            println!("📭 No matching entries found.");
-            return Ok(());
-        }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 
-        println!("📒 Entries:");
-        Self::paginate_entries(entries)?;
-        Ok(())
+    pub fn exists(&self) -> bool {
+        self.path.exists()
     }
 
-    pub fn remove(&self, name: &str, index: Option<usize>) -> Result<()> {
-        let master = self.get_master_password("🔐 Master password: ")?;
-        let mut data = self.load_vault(&master)?;
-
-        match data.entries.get_mut(name) {
-            Some(entry_list) => {
-                if let Some(idx) = index {
-                    if idx == 0 || idx > entry_list.len() {
-                        println!("❌ Invalid index. Use: 1..{}.", entry_list.len());
-                        return Ok(());
-                    }
-                    let removed = entry_list.remove(idx - 1);
-                    println!("🗑️ Removed entry: {} (👤 {})", name, removed.username);
-
-                    if entry_list.is_empty() {
-                        data.entries.remove(name);
-                    }
-                } else {
-                    println!("⚠️  This will remove ALL {} entr{} under '{}'.",
-                             entry_list.len(),
-                             if entry_list.len() > 1 { "ies" } else { "y" },
-                             name);
-
-                    let confirm = Self::prompt_password("Type 'yes' to confirm: ")?;
-                    if confirm.trim() != "yes" {
-                        println!("❌ Cancelled.");
-                        return Ok(());
-                    }
-
-                    data.entries.remove(name);
-                    println!("🗑️ All entries under '{}' removed.", name);
-                }
-
-                Self::save_vault(self.vault_path(), &data, &master)?;
-            }
-            None => {
-                println!("❌ Entry name not found.");
-            }
-        }
+    /// Whether the vault can only be unlocked with a keyfile, as recorded in its header.
+    pub fn requires_keyfile(&self) -> Result<bool> {
+        Ok(crypto::requires_keyfile(&self.read_file(&self.path)?))
+    }
 
-        Ok(())
+    /// Whether the vault has a recovery kit, i.e. `unlock_with_recovery` can open it.
+    pub fn has_recovery(&self) -> Result<bool> {
+        Ok(crypto::has_recovery(&self.read_file(&self.path)?))
     }
 
-    fn load(path: &PathBuf, password: &str) -> Result<VaultData> {
-        let bytes = fs::read(path)?;
-        let data: VaultData = crypto::decrypt(&bytes, password)?;
-        Ok(data)
+    /// The configured keyfile, read now. Fails if there is none but the vault `required` one.
+    fn read_keyfile(&self, required: bool) -> Result<Option<Keyfile>> {
+        match &self.keyfile {
+            Some(path) => Keyfile::read(path).map(Some),
+            None if required => Err(Error::keyfile_required()),
+            None => Ok(None),
+        }
     }
 
-    fn save_vault(path: &PathBuf, data: &VaultData, password: &str) -> Result<()> {
-        let encrypted = crypto::encrypt(data, password)?;
-        fs::create_dir_all(path.parent().unwrap())?;
-        fs::write(path, encrypted)?;
-        Ok(())
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+        fs::read(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::VaultNotFound(path.to_path_buf()),
+            _ => Error::Io(e),
+        })
     }
 
-    fn confirm_overwrite(path: &PathBuf) -> Result<bool> {
-        let confirm = prompt_password("Do you want to overwrite it? Type 'yes' to confirm: ")?;
-        if confirm.trim() != "yes" {
-            return Ok(false);
-        }
+    /// Derives the key from the master password and checks that it opens the vault.
+    pub fn unlock(&self, password: &str) -> Result<MasterKey> {
+        let bytes = self.read_file(&self.path)?;
+        let keyfile = if crypto::requires_keyfile(&bytes) { self.read_keyfile(true)? } else { None };
+        let key = VaultKey::for_vault(&bytes, password, keyfile.as_ref())?;
+        let key = MasterKey::Local(key);
+        self.decrypt(&bytes, &key)?;
+        Ok(key)
+    }
 
-        let old_password = prompt_password("Enter current master password: ")?;
-        match Self::load(path, &old_password) {
-            Ok(_) => {
-                println!("✅ Password confirmed.");
-                Ok(true)
-            }
+    /// Unlocks the vault with the key restored from its recovery shares, bypassing the master
+    /// password and keyfile.
+    pub fn unlock_with_recovery(&self, recovery: &RecoveryKey) -> Result<MasterKey> {
+        let bytes = self.read_file(&self.path)?;
+        let key = VaultKey::for_recovery(&bytes, recovery).map_err(|e| match e {
+            Error::WrongPassword => Error::Recovery("The shares don't belong to this vault".to_string()),
+            e => e,
+        })?;
+        let key = MasterKey::Local(key);
+        self.decrypt(&bytes, &key)?;
+        Ok(key)
+    }
+
+    /// The agent's key for this vault, if it holds one that still opens it. A stale key,
+    /// e.g. from before `passwd` in another process, is dropped.
+    pub fn unlock_with_agent(&self) -> Option<MasterKey> {
+        let bytes = fs::read(&self.path).ok()?;
+        match agent::decrypt(&self.path, &bytes) {
+            Ok(Some(_)) => Some(MasterKey::Agent),
+            Ok(None) => None,
             Err(_) => {
-                println!("❌ Wrong master password. Vault not overwritten.");
-                Ok(false)
+                agent::forget(&self.path);
+                None
             }
         }
     }
 
-    fn copy_to_clipboard(text: &str) -> Result<()> {
-        let mut clipboard = Clipboard::new().map_err(|e| anyhow!("Clipboard error: {e}"))?;
-        clipboard
-            .set_text(text.to_string())
-            .map_err(|e| anyhow!("Failed to copy to clipboard: {e}"))?;
-        Ok(())
+    /// Gives a key from `unlock` to a running agent, so other processes can `unlock_with_agent`.
+    pub fn share_with_agent(&self, key: &MasterKey) -> Result<()> {
+        let MasterKey::Local(key) = key else { return Ok(()) };
+        agent::add_key(&self.path, key)
     }
 
-    fn apply_filter_and_sort(
-        entries: &mut Vec<(&String, &Entry)>,
-        filter: Option<&str>,
-        sort: Option<&str>,
-    ) {
-        if let Some(f) = filter {
-            entries.retain(|(name, entry)| name.contains(f) || entry.username.contains(f));
-        }
+    /// Exclusive lock for a read-modify-write cycle.
+    pub fn lock(&self) -> Result<VaultLock> {
+        VaultLock::exclusive(&self.path, || self.notify_lock_wait())
+    }
 
-        if let Some(sort_key) = sort {
-            match sort_key {
-                "name" => entries.sort_by_key(|(name, _)| *name),
-                "username" => entries.sort_by_key(|(_, entry)| &entry.username),
-                _ => {}
-            }
-        }
+    /// Shared lock for reading.
+    pub fn lock_shared(&self) -> Result<VaultLock> {
+        VaultLock::shared(&self.path, || self.notify_lock_wait())
     }
 
-    fn paginate_entries(entries: Vec<(&String, &Entry)>) -> Result<()> {
-        let term_width = get_terminal_width().saturating_sub(2);
-        let page_size = 10;
+    fn notify_lock_wait(&self) {
+        if let Some(notice) = self.lock_notice {
+            notice();
+        }
+    }
 
-        for (i, (name, entry)) in entries.iter().enumerate() {
-            let mut expired_noti = String::new();
-            let mut remind_noti= String::new();
-            let mut message= String::new();
+    /// Reads and decrypts the vault, without locking it.
+    pub fn load(&self, key: &MasterKey) -> Result<VaultData> {
+        let bytes = self.read_file(&self.path)?;
+        self.decrypt(&bytes, key)
+    }
 
-            if let Some(exp) = entry.expired {
-                let now = Utc::now().timestamp();
-                let diff = exp - now;
-                if diff <= 0 {
-                    expired_noti = format!("\n   ⚠️  Password expired {} day(s) ago.", diff.abs() / 86400);
-                } else {
-                    expired_noti = format!("\n   ⏰ Expires in {} day(s).", diff / 86400);
-                }
-            }
-            if let Some(remind) = entry.remind {
-                let now = Utc::now().timestamp();
-                if remind <= now {
-                    remind_noti = "\n   🔔 Reminder: This password should be reviewed!".to_string();
-                } else {
-                    let diff = remind - now;
-                    remind_noti = format!("\n   🔔 Reminder in {} day(s).", diff / 86400);
-                }
-            }
-            if let Some(msg) = &entry.message {
-                message = format!("\n   📝 Message: {}", msg);
-            }
-            
-            let line = format!("• {} (👤 {}){}{}{}", name, entry.username, expired_noti, remind_noti, message);
+    /// The vault as it is on disk, read under a shared lock.
+    pub fn read(&self, key: &MasterKey) -> Result<VaultData> {
+        let _lock = self.lock_shared()?;
+        self.load(key)
+    }
 
-            for wrapped in wrap(&line, term_width) {
-                println!("{}", wrapped);
-            }
+    /// Applies `change` to the vault as it is on disk under an exclusive lock, saves it and
+    /// returns the saved data. Nothing is written when `change` fails.
+    pub fn update<E: From<Error>>(&self, key: &MasterKey, change: impl FnOnce(&mut VaultData) -> std::result::Result<(), E>) -> std::result::Result<VaultData, E> {
+        let _lock = self.lock()?;
+        let mut data = self.load(key)?;
+        change(&mut data)?;
+        self.save(&data, key)?;
+        Ok(data)
+    }
 
-            if (i + 1) % page_size == 0 && i + 1 < entries.len() {
-                print!("-- More (press any key to continue, q to quit) -- ");
-                io::stdout().flush()?;
-
-                enable_raw_mode()?;
-                let should_quit = if let Event::Key(key_event) = event::read()? {
-                    matches!(key_event.code, KeyCode::Char('q'))
-                } else {
-                    false
-                };
-                disable_raw_mode()?;
-
-                if should_quit {
-                    println!();
-                    break;
-                }
-
-                print!("\r{}\r", " ".repeat(60));
-                io::stdout().flush()?;
-            }
-        }
+    /// Encrypts `data` with the vault's key, keeping its salt so the key (and the agent's
+    /// copy) stays valid, and replaces the file. Doesn't lock.
+    pub fn save(&self, data: &VaultData, key: &MasterKey) -> Result<()> {
+        let payload = Zeroizing::new(serde_json::to_vec(data).map_err(Error::other)?);
+        let encrypted = match key {
+            MasterKey::Local(key) => crypto::encrypt_with_key(&payload, key)?,
+            MasterKey::Agent => agent::encrypt(&self.path, &payload)?.ok_or(Error::Locked)?,
+        };
+        self.write_vault_file(&encrypted)
+    }
 
-        Ok(())
+    /// Saves `data` under a new master password, with a fresh salt, and returns the new key.
+    /// Used for a new vault; it has no recovery kit. The vault requires the configured keyfile from
+    /// then on, or none if there is none. Doesn't lock.
+    pub fn save_with_password(&self, data: &VaultData, password: &str) -> Result<MasterKey> {
+        let keyfile = self.read_keyfile(false)?;
+        let key = MasterKey::Local(VaultKey::generate(password, keyfile.as_ref())?);
+        self.save(data, &key)?;
+        agent::forget(&self.path);
+        Ok(key)
     }
 
+    /// Saves `data` under a new master password and returns the new key. Unlike
+    /// `save_with_password`, a recovery kit stays valid. `key` must be from `unlock` or
+    /// `unlock_with_recovery`, not the agent. Doesn't lock.
+    pub fn change_password(&self, data: &VaultData, key: &MasterKey, password: &str) -> Result<MasterKey> {
+        let keyfile = self.read_keyfile(false)?;
+        let key = MasterKey::Local(local_key(key)?.with_password(password, keyfile.as_ref())?);
+        self.save(data, &key)?;
+        agent::forget(&self.path);
+        Ok(key)
+    }
 
-    pub fn export(&self, path: &str, plain: bool) -> Result<()> {
-        let master = Self::prompt_password("🔐 Master password: ")?;
-        let data = self.load_vault(&master)?;
+    /// Saves `data` with a key that `recovery` can also unwrap, replacing an earlier recovery
+    /// kit, and returns it. `key` must be from `unlock`, not the agent. Doesn't lock.
+    pub fn add_recovery(&self, data: &VaultData, key: &MasterKey, recovery: &RecoveryKey) -> Result<MasterKey> {
+        let key = MasterKey::Local(local_key(key)?.with_recovery(recovery)?);
+        self.save(data, &key)?;
+        agent::forget(&self.path);
+        Ok(key)
+    }
 
-        if plain {
-            let json = serde_json::to_string_pretty(&data)?;
-            fs::write(path, json)?;
-            println!("📄 Vault exported as plain JSON: {}", path);
+    fn decrypt(&self, bytes: &[u8], key: &MasterKey) -> Result<VaultData> {
+        let payload = match key {
+            MasterKey::Local(key) => crypto::decrypt_with_key(bytes, key)?,
+            MasterKey::Agent => agent::decrypt(&self.path, bytes)?.ok_or(Error::Locked)?,
+        };
+        let corrupt = |e: &dyn std::fmt::Display| Error::CorruptVault(e.to_string());
+        if crypto::is_bincode_payload(bytes) {
+            let legacy: LegacyVaultData = bincode::deserialize(&payload).map_err(|e| corrupt(&e))?;
+            Ok(legacy.into())
         } else {
-            let mut safe_data = data.clone();
-
-            for entry_list in safe_data.entries.values_mut() {
-                for entry in entry_list.iter_mut() {
-                    entry.password = crypto::encrypt_entry(&entry.password, &master)?;
-                }
-            }
+            serde_json::from_slice(&payload).map_err(|e| corrupt(&e))
+        }
+    }
 
-            let json = serde_json::to_string_pretty(&safe_data)?;
-            fs::write(path, json)?;
-            println!("🔒 Vault exported (passwords encrypted) to: {}", path);
+    /// Keeps the current vault as `vault.bin.bak.1` (shifting older backups up) and
+    /// atomically replaces it with `bytes`.
+    fn write_vault_file(&self, bytes: &[u8]) -> Result<()> {
+        if self.path.exists() {
+            self.rotate_backups(Self::backup_count())?;
         }
+        write_secret_file(&self.path, bytes)
+    }
 
-        Ok(())
+    fn backup_count() -> usize {
+        env::var("DPLOCK_BACKUP_COUNT")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(5) // Default to 5 backups
     }
 
-    pub fn import(&self, path: &str, plain: bool) -> Result<()> {
-        let json = std::fs::read(path)?;
+    /// `vault.bin.bak.<n>`; 1 is the most recent.
+    pub fn backup_path(&self, n: usize) -> PathBuf {
+        let file_name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        self.path.with_file_name(format!("{}.bak.{}", file_name, n))
+    }
 
-        if is_encrypted(&json) && plain {
-            println!("⚠️ Warning: The file appears to be encrypted, but you are attempting to import it as plain text.");
-            let confirm = prompt_password("Do you want to proceed with plain text import? (yes/no): ")?;
-            if confirm.trim().to_lowercase() != "yes" {
-                println!("❌ Import cancelled.");
-                return Ok(());
+    fn rotate_backups(&self, keep: usize) -> Result<()> {
+        // Drop anything beyond the retention count, including leftovers from a larger setting
+        // and backups past a gap in the numbering.
+        for n in self.backup_numbers()? {
+            if n >= keep.max(1) {
+                fs::remove_file(self.backup_path(n))?;
             }
         }
+        if keep == 0 {
+            return Ok(());
+        }
 
-        let imported_data: VaultData = serde_json::from_slice(&json)?;
+        for n in (1..keep).rev() {
+            let from = self.backup_path(n);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(n + 1))?;
+            }
+        }
+        fs::copy(&self.path, self.backup_path(1))?;
+        Ok(())
+    }
 
-        let source_master = if !plain && is_encrypted(&json) {
-            Self::prompt_password("🔐 Source vault master password: ")?
-        } else {
-            String::new()
+    /// The numbers of the `<vault>.bak.<n>` files next to the vault, in ascending order.
+    pub fn backup_numbers(&self) -> Result<Vec<usize>> {
+        let prefix = format!("{}.bak.", self.path.file_name().unwrap_or_default().to_string_lossy());
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
         };
+        let mut numbers: Vec<usize> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_prefix(&prefix)?.parse().ok())
+            .collect();
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
 
-        let target_vault_path = self.vault_path().clone();
-
-        let target_master = Self::prompt_password("🔐 Target vault master password: ")?;
-
-        let mut current_data = Self::load(&target_vault_path, &target_master)?;
-
-        for (name, new_entries) in imported_data.entries {
-            let entry_list = current_data.entries.entry(name.clone()).or_default();
-            for mut new_entry in new_entries {
-                if !plain {
-                    new_entry.password = crypto::decrypt_entry(&new_entry.password, &source_master)?;
-                }
-                let is_duplicate = entry_list.iter().any(|e| {
-                    e.username == new_entry.username && e.password == new_entry.password
-                });
-                if !is_duplicate {
-                    entry_list.push(new_entry);
-                } else {
-                    println!("⚠️  Duplicate entry skipped: {} / {}", name, new_entry.username);
-                }
-            }
-        }
+    /// Decrypts backup `n` with the vault's current key. Backups from before the last
+    /// password change fail with `WrongPassword`; open them with `Vault::open(backup_path)`.
+    pub fn load_backup(&self, n: usize, key: &MasterKey) -> Result<VaultData> {
+        let bytes = self.read_file(&self.backup_path(n))?;
+        self.decrypt(&bytes, key)
+    }
 
-        Self::save_vault(&target_vault_path, &current_data, &target_master)?;
-        println!("✅ Vault imported and merged successfully into: {}", target_vault_path.display());
+    /// Replaces the vault with backup `n`; the current vault becomes backup 1. Doesn't lock.
+    pub fn restore_backup(&self, n: usize) -> Result<()> {
+        let bytes = self.read_file(&self.backup_path(n))?;
+        self.write_vault_file(&bytes)?;
+        agent::forget(&self.path);
         Ok(())
     }
+}
 
-    fn print_entry_info(entry: &Entry, index: usize, show_password: bool) -> Result<()> {
-        println!("{}. 👤 Username: {}", index + 1, entry.username);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
 
-        if let Some(exp) = entry.expired {
-            let now = Utc::now().timestamp();
-            let diff = exp - now;
+    fn contents(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
 
-            if diff <= 0 {
-                println!("   ⚠️  Password expired {} day(s) ago.", diff.abs() / 86400);
-            } else {
-                println!("   ⏰ Expires in {} day(s).", diff / 86400);
-            }
+    #[test]
+    fn rotate_backups_shifts_and_prunes_past_gaps() {
+        let dir = tempdir().unwrap();
+        let vault = Vault::open(dir.path().join("vault.bin"));
+        fs::write(vault.path(), "current").unwrap();
+        for n in [1, 2, 3, 7] {
+            fs::write(vault.backup_path(n), format!("backup {}", n)).unwrap();
         }
 
-        if let Some(remind) = entry.remind {
-            let now = Utc::now().timestamp();
-            if remind <= now {
-                println!("   🔔 Reminder: This password should be reviewed!");
-            } else {
-                let diff = remind - now;
-                println!("   🔔 Reminder in {} day(s).", diff / 86400);
-            }
-        }
+        vault.rotate_backups(3).unwrap();
+        assert_eq!(vault.backup_numbers().unwrap(), vec![1, 2, 3]);
+        assert_eq!(contents(&vault.backup_path(1)), "current");
+        assert_eq!(contents(&vault.backup_path(2)), "backup 1");
+        assert_eq!(contents(&vault.backup_path(3)), "backup 2");
 
-        if let Some(message) = &entry.message {
-            println!("   📝 Message: {}", message);
+        // A lowered count drops the backups above it.
+        vault.rotate_backups(1).unwrap();
+        assert_eq!(vault.backup_numbers().unwrap(), vec![1]);
+
+        vault.rotate_backups(0).unwrap();
+        assert!(vault.backup_numbers().unwrap().is_empty());
+        assert_eq!(contents(vault.path()), "current");
+    }
+
+    /// `export` encrypts every secret, the TOTP secret too; `import` decrypts them again.
+    #[test]
+    fn encrypted_export_with_totp_imports_again() {
+        let mut entry = Entry::new("alice", "hunter2");
+        entry.totp = Some(Totp::parse("JBSWY3DPEHPK3PXP").unwrap());
+        let mut data = VaultData::default();
+        data.add("github", entry).unwrap();
+
+        for entry in data.entries.values_mut().flatten() {
+            entry.map_secrets(|secret| crypto::encrypt_entry(secret, "master")).unwrap();
         }
+        let json = serde_json::to_vec(&data).unwrap();
+
+        let mut imported: VaultData = serde_json::from_slice(&json).unwrap();
+        let entry = &mut imported.entries.get_mut("github").unwrap()[0];
+        entry.map_secrets(|secret| crypto::decrypt_entry(secret, "master")).unwrap();
+        assert_eq!(entry.password, "hunter2");
+        let totp = entry.totp.as_ref().unwrap();
+        assert_eq!(totp.secret, "JBSWY3DPEHPK3PXP");
+        totp.validate().unwrap();
+    }
 
-        if show_password {
-            println!("   🔑 Password: {}", entry.password);
-        } else {
-            Self::copy_to_clipboard(&entry.password)?;
-            println!("   📋 Password copied to clipboard!");
+    #[test]
+    fn backup_numbers_ignore_other_files() {
+        let dir = tempdir().unwrap();
+        let vault = Vault::open(dir.path().join("vault.bin"));
+        for name in ["vault.bin.bak.10", "vault.bin.bak.2", "vault.bin.bak.x", "other.bin.bak.1", "vault.bin.lock"] {
+            fs::write(vault.path().with_file_name(name), "").unwrap();
         }
+        assert_eq!(vault.backup_numbers().unwrap(), vec![2, 10]);
+    }
 
-        Ok(())
+    #[test]
+    fn names_resolve_exactly_before_normalizing() {
+        let mut data = VaultData::default();
+        data.entries.insert("aws//key".to_string(), vec![Entry::new("legacy", "a")]);
+        data.entries.insert("aws/key".to_string(), vec![Entry::new("modern", "b")]);
+
+        assert_eq!(data.resolve_name("aws//key"), "aws//key");
+        assert_eq!(data.resolve_name(" /aws/key/ "), "aws/key");
+        assert_eq!(data.resolve_name("aws/"), "aws/"); // left for fuzzy matching
+        assert_eq!(data.name_for_add("aws//key").unwrap(), "aws//key");
+        assert_eq!(data.name_for_add("gcp / key/").unwrap(), "gcp/key");
+        assert!(matches!(data.name_for_add("gcp//key"), Err(Error::Parse(_))));
     }
-    pub fn check_reminders(&self) -> Result<()> {
-        println!("💡 Check reminder: this feature is under development and will be available soon!");
-        Ok(())
+
+    #[test]
+    fn saving_keeps_the_vault_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempdir().unwrap();
+        let vault = Vault::open(dir.path().join("vault.bin"));
+        vault.write_vault_file(b"first").unwrap();
+        fs::set_permissions(vault.path(), fs::Permissions::from_mode(0o644)).unwrap();
+        vault.write_vault_file(b"second").unwrap();
+        assert_eq!(fs::metadata(vault.path()).unwrap().permissions().mode() & 0o777, 0o600);
     }
 }